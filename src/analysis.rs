@@ -0,0 +1,112 @@
+use std::f64::consts::TAU;
+use crate::wav::WavData;
+
+pub const ANALYSIS_HOP_SECONDS: f64 = 0.01;
+pub const LOW_CUTOFF_HZ: f64 = 200.0;
+pub const HIGH_CUTOFF_HZ: f64 = 2000.0;
+const ATTACK_SECONDS: f64 = 0.01;
+const RELEASE_SECONDS: f64 = 0.15;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct AudioFeatures {
+    pub envelope: f64,
+    pub low: f64,
+    pub mid: f64,
+    pub high: f64,
+}
+
+// Envelope follower over the full signal and three bands, sampled every hop
+// and normalised to 0..1 over the whole track
+#[derive(Clone)]
+pub struct AudioAnalysis {
+    pub frames: Vec<AudioFeatures>,
+    pub duration: f64,
+    pub hop_seconds: f64, // ANALYSIS_HOP_SECONDS rounded down to whole samples
+}
+
+struct Follower {
+    attack: f64,
+    release: f64,
+    value: f64,
+}
+
+impl Follower {
+    fn new(sample_rate: f64) -> Self {
+        Self {
+            attack: 1.0 - (-1.0 / (ATTACK_SECONDS * sample_rate)).exp(),
+            release: 1.0 - (-1.0 / (RELEASE_SECONDS * sample_rate)).exp(),
+            value: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let x = x.abs();
+        let coeff = if x > self.value { self.attack } else { self.release };
+        self.value += (x - self.value) * coeff;
+        self.value
+    }
+}
+
+fn one_pole_coeff(cutoff: f64, sample_rate: f64) -> f64 {
+    1.0 - (-TAU * cutoff / sample_rate).exp()
+}
+
+impl AudioAnalysis {
+    pub fn analyse(wav: &WavData) -> Self {
+        let rate = wav.sample_rate.max(1) as f64;
+        let hop = ((ANALYSIS_HOP_SECONDS * rate) as usize).max(1);
+        let low_coeff = one_pole_coeff(LOW_CUTOFF_HZ, rate);
+        let high_coeff = one_pole_coeff(HIGH_CUTOFF_HZ, rate);
+
+        let mut followers = [Follower::new(rate), Follower::new(rate), Follower::new(rate), Follower::new(rate)];
+        let (mut low_state, mut high_state) = (0.0, 0.0);
+        let mut frames = Vec::with_capacity(wav.samples.len() / hop + 1);
+
+        for (i, &s) in wav.samples.iter().enumerate() {
+            let x = s as f64;
+            // Band split with two one-pole lowpasses: low | mid | rest
+            low_state += (x - low_state) * low_coeff;
+            high_state += (x - high_state) * high_coeff;
+            let bands = [x, low_state, high_state - low_state, x - high_state];
+            let values: Vec<f64> = followers.iter_mut().zip(bands).map(|(f, b)| f.process(b)).collect();
+
+            if i % hop == 0 {
+                frames.push(AudioFeatures { envelope: values[0], low: values[1], mid: values[2], high: values[3] });
+            }
+        }
+
+        // Normalise each feature by its peak so depths mean the same for any track
+        let peak = |f: fn(&AudioFeatures) -> f64| frames.iter().map(f).fold(0.0, f64::max).max(1e-9);
+        let (pe, pl, pm, ph) = (peak(|f| f.envelope), peak(|f| f.low), peak(|f| f.mid), peak(|f| f.high));
+        for f in &mut frames {
+            f.envelope /= pe;
+            f.low /= pl;
+            f.mid /= pm;
+            f.high /= ph;
+        }
+
+        Self {
+            duration: wav.samples.len() as f64 / rate,
+            frames,
+            hop_seconds: hop as f64 / rate,
+        }
+    }
+
+    // Features at a time in seconds, linearly interpolated; silence past the end
+    pub fn features_at(&self, seconds: f64) -> AudioFeatures {
+        let pos = seconds / self.hop_seconds;
+        if pos < 0.0 || self.frames.is_empty() || pos >= (self.frames.len() - 1) as f64 {
+            return AudioFeatures::default();
+        }
+        let i = pos as usize;
+        let t = pos - i as f64;
+        let (a, b) = (self.frames[i], self.frames[i + 1]);
+        let lerp = |x: f64, y: f64| x + (y - x) * t;
+        AudioFeatures {
+            envelope: lerp(a.envelope, b.envelope),
+            low: lerp(a.low, b.low),
+            mid: lerp(a.mid, b.mid),
+            high: lerp(a.high, b.high),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::cli::Options;
+use crate::game::GameOfLife;
+use crate::history::Snapshot;
+use crate::midi::{MidiBinding, MidiFileSource};
+use crate::modulation::ModMatrix;
+use crate::noise::NoiseModel;
+
+// The start state of the runs of a sweep or search, configured from the
+// options once. Every run copies it instead of applying the options again,
+// which would reopen MIDI ports and re-read and re-analyse audio per run.
+// Live MIDI input and the synth play no part in batch runs, so they are left out
+pub struct BatchTemplate {
+    snapshot: Snapshot,
+    generation: u64,
+    noise_seed: u64,
+    noise: NoiseModel,
+    modulation: ModMatrix,
+    clock: (f64, u32), // BPM and steps per sixteenth
+    midi_file: Option<MidiFileSource>,
+    midi_bindings: Vec<MidiBinding>,
+}
+
+impl BatchTemplate {
+    pub fn new(options: &Options, a_width: u32, a_height: u32) -> Result<Self, String> {
+        let mut game = GameOfLife::new(a_width, a_height, 1);
+        game.apply_options(options)?;
+        Ok(Self {
+            snapshot: game.snapshot(),
+            generation: game.generation,
+            noise_seed: game.noise_seed,
+            noise: game.noise.clone(),
+            modulation: game.modulation.clone(),
+            clock: (game.clock.bpm, game.clock.steps_per_sixteenth),
+            midi_file: options.midi_file.as_deref().map(MidiFileSource::load).transpose()?,
+            midi_bindings: game.midi_bindings.clone(),
+        })
+    }
+
+    // A fresh game in the template's state. The repeat detector is off, as
+    // batch runs only look for repeats once at the end
+    pub fn game(&self) -> GameOfLife {
+        let mut game = GameOfLife::new(self.snapshot.a_width, self.snapshot.a_height, 1);
+        game.restore(self.snapshot.clone());
+        game.generation = self.generation;
+        game.noise_seed = self.noise_seed;
+        game.noise = self.noise.clone();
+        game.modulation = self.modulation.clone();
+        (game.clock.bpm, game.clock.steps_per_sixteenth) = self.clock;
+        game.sync_clock();
+        if let Some(file) = &self.midi_file {
+            game.midi_sources.push(Box::new(file.clone()));
+        }
+        game.midi_bindings = self.midi_bindings.clone();
+        game.period_target = None;
+        game.restart_from_field();
+        game
+    }
+}
@@ -0,0 +1,115 @@
+use crate::utils::bell;
+
+pub const DEFAULT_BRUSH_RADIUS: i32 = 5;
+pub const MAX_BRUSH_RADIUS: i32 = 100;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BrushShape {
+    Circle,
+    Square,
+    Ring,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BrushFalloff {
+    Hard,
+    Gaussian,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BrushMode {
+    Add,
+    Subtract,
+    Set,
+}
+
+pub struct Brush {
+    pub radius: i32,
+    pub shape: BrushShape,
+    pub falloff: BrushFalloff,
+    pub mode: BrushMode,
+    pub value: f64,
+}
+
+impl Brush {
+    pub fn new() -> Self {
+        Self {
+            radius: DEFAULT_BRUSH_RADIUS,
+            shape: BrushShape::Circle,
+            falloff: BrushFalloff::Hard,
+            mode: BrushMode::Set,
+            value: 1.0,
+        }
+    }
+
+    pub fn change_radius(&mut self, delta: i32) {
+        self.radius = (self.radius + delta).clamp(0, MAX_BRUSH_RADIUS);
+    }
+
+    pub fn change_value(&mut self, delta: f64) {
+        self.value = (self.value + delta).clamp(0.0, 1.0);
+    }
+
+    pub fn cycle_shape(&mut self) {
+        self.shape = match self.shape {
+            BrushShape::Circle => BrushShape::Square,
+            BrushShape::Square => BrushShape::Ring,
+            BrushShape::Ring => BrushShape::Circle,
+        };
+    }
+
+    pub fn toggle_falloff(&mut self) {
+        self.falloff = match self.falloff {
+            BrushFalloff::Hard => BrushFalloff::Gaussian,
+            BrushFalloff::Gaussian => BrushFalloff::Hard,
+        };
+    }
+
+    pub fn cycle_mode(&mut self) {
+        self.mode = match self.mode {
+            BrushMode::Add => BrushMode::Subtract,
+            BrushMode::Subtract => BrushMode::Set,
+            BrushMode::Set => BrushMode::Add,
+        };
+    }
+
+    // Strength of the brush at offset (dx, dy) from its center, None outside the footprint
+    pub fn weight(&self, dx: i32, dy: i32) -> Option<f64> {
+        let r = self.radius as f64;
+        let distance = ((dx * dx + dy * dy) as f64).sqrt();
+        let inside = match self.shape {
+            BrushShape::Circle => distance <= r,
+            BrushShape::Square => dx.abs() <= self.radius && dy.abs() <= self.radius,
+            BrushShape::Ring => distance <= r && distance >= r * 0.6,
+        };
+        if !inside {
+            return None;
+        }
+
+        match self.falloff {
+            BrushFalloff::Hard => Some(1.0),
+            BrushFalloff::Gaussian => {
+                // The ring peaks on its middle line, the other shapes at the center
+                let (d, sigma) = match self.shape {
+                    BrushShape::Ring => ((distance - r * 0.8).abs(), r * 0.1),
+                    BrushShape::Circle | BrushShape::Square => (distance, r * 0.5),
+                };
+                Some(bell(d, 0.0, sigma.max(0.5)))
+            }
+        }
+    }
+
+    // New cell value after one dab of strength `weight`; erasing pulls the cell toward 0
+    pub fn apply(&self, current: f64, weight: f64, erase: bool) -> f64 {
+        let new = if erase {
+            current * (1.0 - weight)
+        } else {
+            match self.mode {
+                BrushMode::Add => current + self.value * weight,
+                BrushMode::Subtract => current - self.value * weight,
+                BrushMode::Set => current + (self.value - current) * weight,
+            }
+        };
+        new.clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,54 @@
+pub const MIN_ZOOM: f64 = 0.25;
+pub const MAX_ZOOM: f64 = 64.0;
+pub const ZOOM_STEP: f64 = 1.1;
+
+// Maps field cells to window pixels: screen = cell * zoom + offset.
+// The field keeps its size; only the view changes.
+pub struct Camera {
+    pub zoom: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
+
+impl Camera {
+    pub fn new(zoom: f64) -> Self {
+        Self {
+            zoom,
+            offset_x: 0.0,
+            offset_y: 0.0,
+        }
+    }
+
+    pub fn screen_to_cell(&self, x: i32, y: i32) -> (f64, f64) {
+        ((x as f64 - self.offset_x) / self.zoom, (y as f64 - self.offset_y) / self.zoom)
+    }
+
+    pub fn cell_to_screen(&self, cx: f64, cy: f64) -> (f64, f64) {
+        (cx * self.zoom + self.offset_x, cy * self.zoom + self.offset_y)
+    }
+
+    // Zoom by `factor` keeping the cell under (x, y) fixed on screen
+    pub fn zoom_at(&mut self, x: i32, y: i32, factor: f64) {
+        let (cx, cy) = self.screen_to_cell(x, y);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset_x = x as f64 - cx * self.zoom;
+        self.offset_y = y as f64 - cy * self.zoom;
+    }
+
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        self.offset_x += dx as f64;
+        self.offset_y += dy as f64;
+    }
+
+    // Scale the whole field into the view and center it
+    pub fn fit(&mut self, view_width: u32, view_height: u32, a_width: u32, a_height: u32) {
+        if a_width == 0 || a_height == 0 {
+            return;
+        }
+        self.zoom = (view_width as f64 / a_width as f64)
+            .min(view_height as f64 / a_height as f64)
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset_x = (view_width as f64 - a_width as f64 * self.zoom) / 2.0;
+        self.offset_y = (view_height as f64 - a_height as f64 * self.zoom) / 2.0;
+    }
+}
@@ -0,0 +1,270 @@
+use crate::analysis::AudioAnalysis;
+use crate::game::GameOfLife;
+use crate::midi::{MidiBinding, MidiFileSource, RawPortSource};
+use crate::spectral::SliceMode;
+use crate::sweep::SweepAxis;
+use crate::pattern::Pattern;
+use crate::param_map::MapKind;
+use crate::noise::NoiseModel;
+use crate::init::InitGenerator;
+use crate::image_io::{ImageChannel, ImageMode};
+use crate::npy::{parse_layer_path, NpyDtype};
+use crate::param_map::Layer;
+use crate::params::param_spec;
+use crate::modulation::{Envelope, Lfo, ModRoute};
+use crate::tuning::{ScaleName, Tuning};
+use crate::wav::read_wav;
+
+pub const DEFAULT_HEADLESS_STEPS: u64 = 600;
+pub const DEFAULT_HEADLESS_SIZE: (u32, u32) = (150, 150);
+
+// Command line options; anything not given keeps the interactive defaults
+#[derive(Default)]
+pub struct Options {
+    pub scl: Option<String>,
+    pub kbm: Option<String>,
+    pub scale: Option<ScaleName>,
+    pub root_key: Option<i32>,
+    pub headless: bool,
+    pub steps: Option<u64>,
+    pub size: Option<(u32, u32)>, // Field size in cells for headless runs
+    pub seed: Option<u64>,
+    pub wav: Option<String>,
+    pub fps: Option<f64>, // Generations per second, sets the tempo for the clock's steps per 16th
+    pub bpm: Option<f64>,
+    pub steps_per_sixteenth: Option<u32>,
+    pub routes: Vec<ModRoute>,
+    pub lfos: Vec<Lfo>,
+    pub envelopes: Vec<Envelope>,
+    pub session: Option<String>,
+    pub spectral: Option<SliceMode>,
+    pub follow_creature: bool,
+    pub grain_sample: Option<String>,
+    pub midi_file: Option<String>,
+    pub midi_port: Option<String>,
+    pub midi_bindings: Vec<MidiBinding>,
+    pub sets: Vec<(String, f64)>,
+    pub sweep: Option<(SweepAxis, SweepAxis)>,
+    pub sweep_out: Option<String>,
+    pub search: Option<usize>, // Iterations of the creature search
+    pub search_out: Option<String>,
+    pub pattern: Option<String>,
+    pub pattern_dir: Option<String>, // Where the library looks for .pattern files
+    pub maps: Vec<(MapKind, String)>, // Grayscale images for the parameter maps
+    pub noise: Option<NoiseModel>,
+    pub noise_init: bool, // Start from a sample of the noise model
+    pub init: Option<InitGenerator>,
+    pub image: Option<(String, ImageMode)>,
+    pub field_out: Option<String>, // 16-bit PGM of the last headless generation
+    pub npy_in: Vec<(Layer, String)>,
+    pub npy_out: Vec<(Layer, String)>, // Written after the last headless generation
+    pub npy_stack: Option<String>,     // Every headless generation in one (frames, height, width) array
+    pub npy_f32: bool,
+    pub frames_out: Option<String>,
+    pub audio_out: Option<String>,
+}
+
+pub fn usage() -> &'static str {
+    "usage: lenia [--scl FILE.scl] [--kbm FILE.kbm] [--scale NAME] [--root MIDI_NOTE]\n\
+     \x20            [--wav TRACK.wav] [--bpm N] [--steps-per-16th K] [--fps N]\n\
+     \x20            [--mod SOURCE:TARGET:DEPTH]... [--seed N]\n\
+     \x20            [--lfo SHAPE:PERIOD]... [--env A:D:S:H:R:TRIGGER]... [--session FILE]\n\
+     \x20            [--midi-file FILE.mid] [--midi-port DEVICE] [--midi-map CONTROL:TARGET]...\n\
+     \x20            [--spectral row|column|radial] [--follow] [--grains SAMPLE.wav]\n\
+     \x20            [--set PARAM=VALUE]... [--map m|s|dt=IMAGE.pgm]...\n\
+     \x20            [--noise MODEL[:KEY=VALUE]...] [--noise-init] [--init KIND[:KEY=VALUE]...]\n\
+     \x20            [--image FILE[:luma|red|green|blue|rgb]] [--npy [m|s|dt=]FILE.npy]...\n\
+     \x20            [--sweep X_PARAM:MIN:MAX:N Y_PARAM:MIN:MAX:N [--sweep-out PREFIX]]\n\
+     \x20            [--search ITERATIONS [--search-out DIR]] [--pattern FILE.pattern]\n\
+     \x20            [--patterns DIR]\n\
+     \x20            [--headless [--steps N] [--size WxH] [--frames-out DIR] [--audio-out FILE.wav]\n\
+     \x20             [--field-out FILE.pgm] [--npy-out [m|s|dt=]FILE.npy]...\n\
+     \x20             [--npy-stack FILE.npy] [--npy-f32]]"
+}
+
+impl Options {
+    pub fn npy_dtype(&self) -> NpyDtype {
+        if self.npy_f32 { NpyDtype::F32 } else { NpyDtype::F64 }
+    }
+}
+
+fn parse_size(text: &str) -> Option<(u32, u32)> {
+    let (w, h) = text.split_once('x')?;
+    Some((w.parse().ok()?, h.parse().ok()?))
+}
+
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--scl" => options.scl = Some(value()?),
+            "--kbm" => options.kbm = Some(value()?),
+            "--scale" => {
+                let name = value()?;
+                options.scale = Some(ScaleName::from_name(&name).ok_or(format!("unknown scale '{}'", name))?);
+            }
+            "--root" => options.root_key = Some(value()?.parse().map_err(|_| "--root needs a MIDI note number")?),
+            "--headless" => options.headless = true,
+            "--steps" => options.steps = Some(value()?.parse().map_err(|_| "--steps needs a number")?),
+            "--size" => options.size = Some(parse_size(&value()?).ok_or("--size needs WIDTHxHEIGHT")?),
+            "--seed" => options.seed = Some(value()?.parse().map_err(|_| "--seed needs a number")?),
+            "--wav" => options.wav = Some(value()?),
+            "--bpm" => options.bpm = Some(value()?.parse().map_err(|_| "--bpm needs a number")?),
+            "--steps-per-16th" => options.steps_per_sixteenth = Some(value()?.parse().map_err(|_| "--steps-per-16th needs a number")?),
+            "--fps" => options.fps = Some(value()?.parse().map_err(|_| "--fps needs a number")?),
+            "--mod" => options.routes.push(ModRoute::parse(&value()?)?),
+            "--lfo" => options.lfos.push(Lfo::parse(&value()?)?),
+            "--env" => options.envelopes.push(Envelope::parse(&value()?)?),
+            "--session" => options.session = Some(value()?),
+            "--spectral" => {
+                let name = value()?;
+                options.spectral = Some(SliceMode::from_name(&name).ok_or(format!("unknown slice '{}'", name))?);
+            }
+            "--follow" => options.follow_creature = true,
+            "--grains" => options.grain_sample = Some(value()?),
+            "--midi-file" => options.midi_file = Some(value()?),
+            "--midi-port" => options.midi_port = Some(value()?),
+            "--midi-map" => options.midi_bindings.push(MidiBinding::parse(&value()?)?),
+            "--set" => {
+                let text = value()?;
+                let (name, v) = text.split_once('=').ok_or("--set needs PARAM=VALUE")?;
+                if param_spec(name).is_none() {
+                    return Err(format!("unknown parameter '{}'", name));
+                }
+                options.sets.push((name.to_string(), v.parse().map_err(|_| format!("bad value '{}'", v))?));
+            }
+            "--map" => {
+                let text = value()?;
+                let (kind, path) = text.split_once('=').ok_or("--map needs m|s|dt=IMAGE")?;
+                options.maps.push((MapKind::from_name(kind).ok_or(format!("unknown map '{}'", kind))?, path.to_string()));
+            }
+            "--noise" => options.noise = Some(NoiseModel::parse(&value()?)?),
+            "--noise-init" => options.noise_init = true,
+            "--init" => options.init = Some(InitGenerator::parse(&value()?)?),
+            "--image" => {
+                let text = value()?;
+                // A trailing ":mode" only counts when it names one, so paths may contain ':'
+                let (path, mode) = match text.rsplit_once(':').and_then(|(p, m)| ImageMode::from_name(m).map(|m| (p, m))) {
+                    Some((path, mode)) => (path.to_string(), mode),
+                    None => (text, ImageMode::Channel(ImageChannel::Luma)),
+                };
+                options.image = Some((path, mode));
+            }
+            "--field-out" => options.field_out = Some(value()?),
+            "--npy" => options.npy_in.push(parse_layer_path(&value()?)?),
+            "--npy-out" => options.npy_out.push(parse_layer_path(&value()?)?),
+            "--npy-stack" => options.npy_stack = Some(value()?),
+            "--npy-f32" => options.npy_f32 = true,
+            "--sweep" => {
+                let x = SweepAxis::parse(&value()?)?;
+                let y = SweepAxis::parse(&value()?)?;
+                options.sweep = Some((x, y));
+                options.headless = true;
+            }
+            "--sweep-out" => options.sweep_out = Some(value()?),
+            "--search" => {
+                options.search = Some(value()?.parse().map_err(|_| "--search needs a number of iterations")?);
+                options.headless = true;
+            }
+            "--search-out" => options.search_out = Some(value()?),
+            "--pattern" => options.pattern = Some(value()?),
+            "--patterns" => options.pattern_dir = Some(value()?),
+            "--frames-out" => options.frames_out = Some(value()?),
+            "--audio-out" => options.audio_out = Some(value()?),
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+    Ok(options)
+}
+
+impl GameOfLife {
+    // Settings shared by the interactive and headless modes
+    pub fn apply_options(&mut self, options: &Options) -> Result<(), String> {
+        // The session goes first so the other options can override it
+        if let Some(session) = &options.session {
+            self.load_session(session)?;
+        }
+        if let Some(init) = &options.init {
+            self.init = init.clone();
+            self.new_world();
+        }
+        if let Some((path, mode)) = &options.image {
+            self.load_field_image(path, *mode)?;
+        }
+        for (layer, path) in &options.npy_in {
+            self.load_npy(*layer, path)?;
+        }
+        if let Some(dir) = &options.pattern_dir {
+            self.library.dir = dir.clone();
+        }
+        if let Some(path) = &options.pattern {
+            self.load_pattern(&Pattern::load(path)?);
+        }
+        for (name, value) in &options.sets {
+            self.set_parameter(name, *value);
+        }
+        for (kind, path) in &options.maps {
+            self.load_param_map(*kind, path)?;
+        }
+        if let Some(scl) = &options.scl {
+            self.quantiser.tuning = Tuning::load_scala(scl, options.kbm.as_deref())?;
+        }
+        if let Some(scale) = options.scale {
+            self.quantiser.scale = scale;
+        }
+        if let Some(root_key) = options.root_key {
+            self.quantiser.root_key = root_key;
+        }
+        if let Some(seed) = options.seed {
+            self.noise_seed = seed;
+            self.modulation.reseed(seed);
+        }
+        if let Some(noise) = &options.noise {
+            self.noise = noise.clone();
+        }
+        // After the seed, so a model without its own seed follows --seed
+        if options.noise_init {
+            self.fill_with_noise();
+        }
+        if let Some(wav) = &options.wav {
+            self.modulation.audio = Some(AudioAnalysis::analyse(&read_wav(wav)?));
+        }
+        if let Some(k) = options.steps_per_sixteenth {
+            self.clock.steps_per_sixteenth = k.max(1);
+        }
+        if let Some(bpm) = options.bpm {
+            self.clock.set_bpm(bpm);
+        }
+        if let Some(fps) = options.fps {
+            if fps <= 0.0 {
+                return Err("--fps must be positive".to_string());
+            }
+            self.clock.set_steps_per_second(fps);
+        }
+        self.sync_clock();
+        self.modulation.lfos.extend(options.lfos.iter().cloned());
+        self.modulation.envelopes.extend(options.envelopes.iter().cloned());
+        self.modulation.routes.extend(options.routes.iter().cloned());
+        if let Some(path) = &options.midi_file {
+            self.midi_sources.push(Box::new(MidiFileSource::load(path)?));
+        }
+        if let Some(path) = &options.midi_port {
+            self.midi_sources.push(Box::new(RawPortSource::open(path)?));
+        }
+        self.midi_bindings.extend(options.midi_bindings.iter().cloned());
+        if options.spectral.is_some() {
+            self.spectral = options.spectral;
+        }
+        self.follow_creature |= options.follow_creature;
+        if let Some(path) = &options.grain_sample {
+            let wav = read_wav(path)?;
+            if let Ok(mut synth) = self.synth.lock() {
+                synth.granular.load(wav);
+            }
+            self.granular.enabled = true;
+        }
+        self.sync_synth_mode();
+        Ok(())
+    }
+}
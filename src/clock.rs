@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_BPM: f64 = 120.0;
+pub const DEFAULT_STEPS_PER_SIXTEENTH: u32 = 1;
+pub const BEATS_PER_BAR: u64 = 4;
+pub const STEPS_PER_SIXTEENTH_CHOICES: [u32; 5] = [1, 2, 3, 4, 8];
+const MIN_BPM: f64 = 1.0;
+const MIN_TAP_BPM: f64 = 20.0;
+const MAX_TAP_BPM: f64 = 300.0;
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_TAPS: usize = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BeatPosition {
+    pub bar: u64,
+    pub beat: u64,      // Within the bar
+    pub sixteenth: u64, // Within the beat
+}
+
+// Musical tempo the simulation steps are locked to. Positions are counted in
+// generations rather than wall-clock time, so rewinds, headless renders and
+// the interactive view all agree on where the downbeats are
+pub struct Clock {
+    pub bpm: f64,
+    pub steps_per_sixteenth: u32,
+    taps: Vec<Instant>,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            bpm: DEFAULT_BPM,
+            steps_per_sixteenth: DEFAULT_STEPS_PER_SIXTEENTH,
+            taps: Vec::new(),
+        }
+    }
+
+    pub fn steps_per_beat(&self) -> f64 {
+        4.0 * self.steps_per_sixteenth as f64
+    }
+
+    pub fn steps_per_second(&self) -> f64 {
+        self.bpm / 60.0 * self.steps_per_beat()
+    }
+
+    pub fn seconds_per_step(&self) -> f64 {
+        1.0 / self.steps_per_second()
+    }
+
+    // Pick the tempo that gives this many steps per second
+    pub fn set_steps_per_second(&mut self, steps: f64) {
+        self.set_bpm(steps * 60.0 / self.steps_per_beat());
+    }
+
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.bpm = bpm.max(MIN_BPM);
+    }
+
+    pub fn position(&self, generation: u64) -> BeatPosition {
+        let k = self.steps_per_sixteenth.max(1) as u64;
+        let sixteenths = generation / k;
+        let beats = sixteenths / 4;
+        BeatPosition {
+            bar: beats / BEATS_PER_BAR,
+            beat: beats % BEATS_PER_BAR,
+            sixteenth: sixteenths % 4,
+        }
+    }
+
+    pub fn on_sixteenth(&self, generation: u64) -> bool {
+        generation.is_multiple_of(self.steps_per_sixteenth.max(1) as u64)
+    }
+
+    pub fn on_beat(&self, generation: u64) -> bool {
+        generation.is_multiple_of(4 * self.steps_per_sixteenth.max(1) as u64)
+    }
+
+    pub fn on_bar(&self, generation: u64) -> bool {
+        self.on_beat(generation) && self.position(generation).beat == 0
+    }
+
+    // Tap tempo: the average interval of the recent taps becomes the beat.
+    // A pause longer than TAP_TIMEOUT starts a new run of taps
+    pub fn tap(&mut self, now: Instant) {
+        if self.taps.last().is_some_and(|&t| now.duration_since(t) > TAP_TIMEOUT) {
+            self.taps.clear();
+        }
+        self.taps.push(now);
+        if self.taps.len() > MAX_TAPS {
+            self.taps.remove(0);
+        }
+        if self.taps.len() >= 2 {
+            let span = now.duration_since(self.taps[0]).as_secs_f64();
+            self.set_bpm((60.0 * (self.taps.len() - 1) as f64 / span).clamp(MIN_TAP_BPM, MAX_TAP_BPM));
+        }
+    }
+
+    pub fn cycle_steps_per_sixteenth(&mut self) {
+        let idx = STEPS_PER_SIXTEENTH_CHOICES.iter().position(|&k| k == self.steps_per_sixteenth).unwrap_or(0);
+        self.steps_per_sixteenth = STEPS_PER_SIXTEENTH_CHOICES[(idx + 1) % STEPS_PER_SIXTEENTH_CHOICES.len()];
+    }
+}
@@ -1,19 +1,45 @@
 use sdl2::pixels::Color;
 use sdl2::video::Window;
-use rand::{Rng, rngs::StdRng, SeedableRng};
 use rayon::prelude::*;
-use std::time::{Instant, Duration};
+use std::time::Instant;
 use colorgrad::{self, Gradient};
 use colorgrad::preset::{viridis, inferno, plasma, magma, rainbow};
 use crate::utils::{growth};
+use crate::camera::Camera;
+use crate::brush::Brush;
+use crate::history::{History, DEFAULT_HISTORY_BYTES};
+use crate::rewind::{RewindBuffer, DEFAULT_REWIND_FRAMES};
+use crate::integrate::Integrator;
+use crate::stats::{Observables, StatsLog};
+use crate::tracking::{CreatureEvent, Tracker, DEFAULT_TRACK_THRESHOLD};
+use crate::synth::Synth;
+use crate::tuning::{Quantiser, Tuning};
+use crate::modulation::{EnvelopeTrigger, ModMatrix};
+use crate::clock::Clock;
+use crate::midi::{MidiAction, MidiBinding, MidiMessage, MidiSource};
+use crate::periodicity::{PeriodTarget, Periodicity, DETECT_INTERVAL};
+use crate::param_map::{factor, Layer, MapKind, ParamMaps, NEUTRAL};
+use crate::mask::CellType;
+use crate::noise::{NoiseKind, NoiseModel};
+use crate::init::{InitGenerator, InitKind};
+use crate::library::Library;
+use crate::spectral::SliceMode;
+use crate::granular::Granular;
+use crate::params::param_spec;
+use std::sync::{Arc, Mutex};
 
 pub const DEFAULT_PIXEL_EDGE_SIZE: u32 = 5;
 pub const DEFAULT_UPDATE_FREQ: f64 = 10.0;
+pub const DEFAULT_DT: f64 = 1.0 / DEFAULT_UPDATE_FREQ;
 pub const DEFAULT_KERNEL_RAD: u32 = 13;
 pub const DEFAULT_BELL_M: f64 = 0.12;
 pub const DEFAULT_BELL_S: f64 = 0.015;
 pub const DEFAULT_INFO_BAR_HEIGHT: u32 = 100;
 pub const DEFAULT_NOISE_INTENSITY: f64 = 0.1;
+pub const DEFAULT_SEED: u64 = 42;
+pub const STATS_CSV_PATH: &str = "stats.csv";
+pub const SPEEDS: [f64; 4] = [0.5, 1.0, 2.0, 4.0]; // Multiples of the clock tempo
+const MAX_STEPS_PER_UPDATE: f64 = 16.0; // Drop steps rather than stall after a hitch
 
 pub struct GameOfLife {
     pub pxl_vec: Vec<f64>,
@@ -23,13 +49,14 @@ pub struct GameOfLife {
     pub generation: u64,
     pub colors: Vec<Color>, // Precomputed colors for faster lookup
     pub smooth_edges: bool,
-    pub update_skip_counter: usize,
     pub width: u32,
     pub height: u32,
     pub a_width: u32,
     pub a_height: u32,
     pub pixel_edge_size: u32,
     pub update_freq: f64,
+    pub dt: f64, // Integration step, 1/update_freq unless set directly
+    pub integrator: Integrator,
     pub kernel_rad: u32,
     pub bell_m: f64,
     pub bell_s: f64,
@@ -37,22 +64,55 @@ pub struct GameOfLife {
     pub gradient_idx: usize,
     pub gradients: Vec<Box<dyn Gradient>>,
     pub info_window: Option<Window>,
+    pub slider_y_start: i32, // Where the info window placed its first slider
     pub mouse_down: bool, // Track if mouse button is held down
+    pub erasing: bool, // The held button is the right one
     pub noise_intensity: f64,
     pub noise_enabled: bool,
+    pub camera: Camera,
+    pub panning: bool, // Middle button drag moves the camera
+    pub mouse_pos: (i32, i32),
+    pub ctrl_held: bool,
+    pub brush: Brush,
+    pub history: History,
+    pub speed: f64,
+    pub step_accumulator: f64,
+    pub rewind: RewindBuffer,
+    pub stats: StatsLog,
+    pub wrap_edges: bool, // Treat the field as a torus
+    pub tracker: Tracker,
+    pub show_creatures: bool,
+    pub synth: Arc<Mutex<Synth>>, // Shared with the audio callback
+    pub quantiser: Quantiser, // Pitch mapping shared by all sonifiers
+    pub noise_seed: u64,
+    pub modulation: ModMatrix,
+    pub clock: Clock,
+    pub pending_notes: Vec<u64>, // Creatures born off the 16th grid, waiting to sound
+    pub midi_sources: Vec<Box<dyn MidiSource>>,
+    pub midi_bindings: Vec<MidiBinding>,
+    pub midi_learn: Option<MidiAction>, // Target for the next control that moves
+    pub last_midi: Option<MidiMessage>,
+    pub spectral: Option<SliceMode>, // Spectral synthesis from a field slice instead of voices
+    pub follow_creature: bool, // The slice follows the heaviest creature instead of sweeping
+    pub granular: Granular,
+    pub period_target: Option<PeriodTarget>, // What the repeat detector watches, None when off
+    pub periodicity: Option<Periodicity>,
+    pub param_maps: ParamMaps,
+    pub layer: Layer, // What the brush paints and the view shows
+    pub mask: Option<Vec<CellType>>,
+    pub mask_brush: CellType,
+    pub noise: NoiseModel,
+    pub init: InitGenerator, // Fills new and reset worlds
+    pub library: Library,
 }
 
 impl GameOfLife {
-    pub fn new(width: u32, height: u32, pixel_edge_size: u32, video_subsystem: &sdl2::VideoSubsystem) -> Self {
+    pub fn new(width: u32, height: u32, pixel_edge_size: u32) -> Self {
         let a_width = width / pixel_edge_size;
         let a_height = height / pixel_edge_size;
-        let a_size = (a_width * a_height) as usize;
 
-        let mut pxl_vec = vec![0.0; a_size];
-        let mut rng = StdRng::seed_from_u64(42);
-        pxl_vec.iter_mut().for_each(|i| {
-            *i = rng.gen();
-        });
+        let init = InitGenerator::new(InitKind::Uniform);
+        let pxl_vec = init.generate(a_width, a_height);
 
         let gradients: Vec<Box<dyn Gradient>> = vec![
             Box::new(viridis()),
@@ -65,6 +125,12 @@ impl GameOfLife {
 
         let colors = Self::compute_colors(&**gradient);
 
+        let mut rewind = RewindBuffer::new(DEFAULT_REWIND_FRAMES);
+        rewind.push(0, &pxl_vec);
+
+        let mut camera = Camera::new(pixel_edge_size as f64);
+        camera.fit(width, height, a_width, a_height);
+
         Self {
             pxl_vec,
             last_update: Instant::now(),
@@ -73,13 +139,14 @@ impl GameOfLife {
             generation: 0,
             colors,
             smooth_edges: false,
-            update_skip_counter: 0,
             width,
             height,
             a_width,
             a_height,
             pixel_edge_size,
             update_freq: DEFAULT_UPDATE_FREQ,
+            dt: DEFAULT_DT,
+            integrator: Integrator::Euler,
             kernel_rad: DEFAULT_KERNEL_RAD,
             bell_m: DEFAULT_BELL_M,
             bell_s: DEFAULT_BELL_S,
@@ -88,8 +155,45 @@ impl GameOfLife {
             gradient_idx: 0,
             gradients,
             info_window: None,
+            slider_y_start: 0,
             mouse_down: false,
+            erasing: false,
             noise_enabled: true,
+            camera,
+            panning: false,
+            mouse_pos: (0, 0),
+            ctrl_held: false,
+            brush: Brush::new(),
+            history: History::new(DEFAULT_HISTORY_BYTES),
+            speed: 1.0,
+            step_accumulator: 0.0,
+            rewind,
+            stats: StatsLog::new(),
+            wrap_edges: false,
+            tracker: Tracker::new(DEFAULT_TRACK_THRESHOLD),
+            show_creatures: false,
+            synth: Synth::shared(),
+            quantiser: Quantiser::new(Tuning::equal_temperament(12)),
+            noise_seed: DEFAULT_SEED,
+            modulation: ModMatrix::new(DEFAULT_SEED),
+            clock: Clock::new(),
+            pending_notes: Vec::new(),
+            midi_sources: Vec::new(),
+            midi_bindings: Vec::new(),
+            midi_learn: None,
+            last_midi: None,
+            spectral: None,
+            follow_creature: false,
+            granular: Granular::new(a_width, a_height),
+            period_target: Some(PeriodTarget::Field),
+            periodicity: None,
+            param_maps: ParamMaps::default(),
+            layer: Layer::Field,
+            mask: None,
+            mask_brush: CellType::Wall,
+            noise: NoiseModel::new(NoiseKind::Uniform),
+            init,
+            library: Library::new(),
         }
     }
 
@@ -102,59 +206,212 @@ impl GameOfLife {
             .collect()
     }
 
+    // Run as many steps as the tempo clock has ticked since the last update
     pub fn update(&mut self) {
-        if self.running && self.update_skip_counter % 10 == 0 && self.last_update.elapsed() >= Duration::from_millis(16) {
-            let mut new_pxl_vec = self.pxl_vec.clone();
-            new_pxl_vec.par_iter_mut().enumerate().for_each(|(i, val)| {
-                let x = (i % self.a_width as usize) as i32;
-                let y = (i / self.a_width as usize) as i32;
-                let mut neighbours = 0.0;
-                let mut count = 0;
-                let kernel_radius = self.kernel_rad as i32;
-
-                for dy in -kernel_radius..=kernel_radius {
-                    for dx in -kernel_radius..=kernel_radius {
-                        let nx = x + dx;
-                        let ny = y + dy;
-                        if nx >= 0 && ny >= 0 && (nx as u32) < self.a_width && (ny as u32) < self.a_height {
-                            neighbours += self.pxl_vec[(ny as usize) * self.a_width as usize + (nx as usize)];
-                            count += 1;
+        let elapsed = self.last_update.elapsed().as_secs_f64();
+        self.last_update = Instant::now();
+        if elapsed > 0.0 {
+            self.fps = (1.0 / elapsed) as f32;
+        }
+        self.process_midi();
+        if !self.running {
+            return;
+        }
+        self.step_accumulator += elapsed * self.clock.steps_per_second() * self.speed;
+        self.step_accumulator = self.step_accumulator.min(MAX_STEPS_PER_UPDATE);
+        while self.step_accumulator >= 1.0 {
+            self.step();
+            self.step_accumulator -= 1.0;
+        }
+    }
+
+    // The modulation sources keep their own copy of the timebase
+    pub fn sync_clock(&mut self) {
+        self.modulation.seconds_per_step = self.clock.seconds_per_step();
+        self.modulation.steps_per_beat = self.clock.steps_per_beat();
+    }
+
+    pub fn tap_tempo(&mut self) {
+        self.clock.tap(Instant::now());
+        self.sync_clock();
+    }
+
+    pub fn cycle_steps_per_sixteenth(&mut self) {
+        self.clock.cycle_steps_per_sixteenth();
+        self.sync_clock();
+    }
+
+    // Advance the field by exactly one generation
+    pub fn step(&mut self) {
+        self.process_midi();
+        let base_parameters = self.apply_modulation();
+
+        let mut growth_mean = None;
+        let dt_map = self.param_maps.get(MapKind::Dt, self.pxl_vec.len());
+        let mask = self.mask_cells();
+        let mut new_pxl_vec = self.integrator.integrate(&self.pxl_vec, self.dt, |field| {
+            let mut rate = self.growth_field(field);
+            // A local time step is the same as a locally scaled rate, which
+            // keeps every integrator correct
+            if let Some(map) = dt_map {
+                rate.iter_mut().zip(map).for_each(|(r, &v)| *r *= factor(v));
+            }
+            // Masked cells keep their value through every integrator stage
+            if let Some(mask) = mask {
+                rate.iter_mut().zip(mask).filter(|(_, c)| **c != CellType::Normal).for_each(|(r, _)| *r = 0.0);
+            }
+            // Report the growth of the state we started from, not of the RK stages
+            growth_mean.get_or_insert_with(|| rate.iter().sum::<f64>() / rate.len().max(1) as f64);
+            rate
+        });
+
+        // Noise is a Wiener increment, so its spread grows with sqrt(dt). It is
+        // seeded per generation so runs are reproducible
+        if self.noise_enabled {
+            let amplitude = self.noise_intensity * self.dt.sqrt();
+            self.noise.apply(&mut new_pxl_vec, self.a_width, self.a_height, self.generation, amplitude, self.noise_seed);
+        }
+        new_pxl_vec.iter_mut().for_each(|val| *val = val.clamp(0.0, 1.0));
+
+        self.pxl_vec = new_pxl_vec;
+        self.apply_mask();
+        self.generation += 1;
+        self.rewind.push(self.generation, &self.pxl_vec);
+
+        let observables = Observables::compute(&self.pxl_vec, self.a_width, self.generation, growth_mean.unwrap_or(0.0), self.stats.latest());
+        self.stats.push(observables);
+        self.tracker.update(&self.pxl_vec, self.a_width, self.a_height, self.wrap_edges, self.generation);
+        if self.tracker.events.iter().any(|e| matches!(e, CreatureEvent::Birth { .. })) {
+            self.modulation.trigger_envelopes(EnvelopeTrigger::Birth);
+        }
+        if self.clock.on_beat(self.generation) {
+            self.modulation.trigger_envelopes(EnvelopeTrigger::Beat);
+        }
+        if self.clock.on_bar(self.generation) {
+            self.modulation.trigger_envelopes(EnvelopeTrigger::Bar);
+        }
+        self.sonify_creatures();
+        self.sonify_spectrum();
+        self.sonify_grains();
+        // The sonifiers read modulated parameters too, so restore only after them
+        self.restore_modulation(base_parameters);
+        if let Some(target) = self.period_target {
+            if self.generation.is_multiple_of(DETECT_INTERVAL) {
+                self.periodicity = self.detect_periodicity(target);
+            }
+        }
+    }
+
+    // Growth rate dA/dt of every cell for the given field
+    pub fn growth_field(&self, field: &[f64]) -> Vec<f64> {
+        let (a_width, a_height) = (self.a_width, self.a_height);
+        let wrap = self.wrap_edges;
+        let kernel_radius = self.kernel_rad as i32;
+        let (bell_m, bell_s) = (self.bell_m, self.bell_s);
+        let m_map = self.param_maps.get(MapKind::M, field.len());
+        let s_map = self.param_maps.get(MapKind::S, field.len());
+        // A map value of 0 would give a zero-width bell, which is 0/0 on an empty neighbourhood
+        let min_m = param_spec("bell_m").map_or(0.0, |p| p.min);
+        let min_s = param_spec("bell_s").map_or(0.0, |p| p.min);
+        let mask = self.mask_cells();
+
+        (0..field.len()).into_par_iter().map(|i| {
+            let x = (i % a_width as usize) as i32;
+            let y = (i / a_width as usize) as i32;
+            let mut neighbours = 0.0;
+            let mut count = 0;
+
+            for dy in -kernel_radius..=kernel_radius {
+                for dx in -kernel_radius..=kernel_radius {
+                    let (nx, ny) = if wrap {
+                        ((x + dx).rem_euclid(a_width as i32), (y + dy).rem_euclid(a_height as i32))
+                    } else {
+                        (x + dx, y + dy)
+                    };
+                    if nx >= 0 && ny >= 0 && (nx as u32) < a_width && (ny as u32) < a_height {
+                        let index = (ny as usize) * a_width as usize + (nx as usize);
+                        if mask.is_some_and(|m| m[index] == CellType::Wall) {
+                            continue;
                         }
+                        neighbours += field[index];
+                        count += 1;
                     }
                 }
+            }
 
-                if count > 0 {
-                    neighbours /= count as f64;
-                }
+            if count > 0 {
+                neighbours /= count as f64;
+            }
 
-                let mut rng = StdRng::from_entropy();
-                let noise = if self.noise_enabled {
-                    rng.gen_range(-self.noise_intensity..self.noise_intensity)
-                } else {
-                    0.0
-                };
-                *val = (*val + noise + ((1.0 / self.update_freq) * growth(neighbours, self.bell_m, self.bell_s))).clamp(0.0, 1.0);
-            });
+            let m = m_map.map_or(bell_m, |map| (bell_m * factor(map[i])).max(min_m));
+            let s = s_map.map_or(bell_s, |map| (bell_s * factor(map[i])).max(min_s));
+            growth(neighbours, m, s)
+        }).collect()
+    }
+
+    // Start the history-dependent state over after the field was replaced
+    pub fn restart_from_field(&mut self) {
+        self.apply_mask();
+        self.rewind.clear();
+        self.rewind.push(self.generation, &self.pxl_vec);
+        self.tracker.clear();
+        self.pending_notes.clear();
+        self.periodicity = None;
+    }
 
-            self.pxl_vec = new_pxl_vec;
-            self.generation += 1;
-            self.fps = 1000.0 / (self.last_update.elapsed().as_millis() as f32);
-            self.last_update = Instant::now();
+    // Single step while paused, replaying rewound frames before simulating new ones
+    pub fn step_forward(&mut self) {
+        if let Some((generation, field)) = self.rewind.forward(1) {
+            self.generation = *generation;
+            self.pxl_vec = field.clone();
+        } else {
+            self.step();
         }
-        self.update_skip_counter += 1;
     }
 
-    pub fn add_cells_with_brush(&mut self, mouse_x: i32, mouse_y: i32, brush_radius: i32, state: f64) {
+    pub fn step_back(&mut self, steps: usize) {
+        if let Some((generation, field)) = self.rewind.back(steps) {
+            self.generation = *generation;
+            self.pxl_vec = field.clone();
+        }
+    }
+
+    pub fn change_speed(&mut self, faster: bool) {
+        let idx = SPEEDS.iter().position(|&s| s == self.speed).unwrap_or(1);
+        let idx = if faster { (idx + 1).min(SPEEDS.len() - 1) } else { idx.saturating_sub(1) };
+        self.speed = SPEEDS[idx];
+        self.step_accumulator = 0.0;
+    }
+
+    pub fn add_cells_with_brush(&mut self, mouse_x: i32, mouse_y: i32, erase: bool) {
+        let (cx, cy) = self.camera.screen_to_cell(mouse_x, mouse_y);
+        self.paint_cells(cx.floor() as i32, cy.floor() as i32, erase);
+    }
+
+    // One brush dab centered on cell (cx, cy)
+    pub fn paint_cells(&mut self, cx: i32, cy: i32, erase: bool) {
+        let brush_radius = self.brush.radius;
         for dy in -brush_radius..=brush_radius {
             for dx in -brush_radius..=brush_radius {
-                let nx = mouse_x / self.pixel_edge_size as i32 + dx;
-                let ny = mouse_y / self.pixel_edge_size as i32 + dy;
+                let nx = cx + dx;
+                let ny = cy + dy;
 
                 if nx >= 0 && ny >= 0 && (nx as u32) < self.a_width && (ny as u32) < self.a_height {
-                    let distance = ((dx * dx + dy * dy) as f64).sqrt();
-                    if distance <= brush_radius as f64 {
-                        let index = (ny as usize * self.a_width as usize + nx as usize) as usize;
-                        self.pxl_vec[index] = state;
+                    if let Some(weight) = self.brush.weight(dx, dy) {
+                        let index = ny as usize * self.a_width as usize + nx as usize;
+                        match self.layer {
+                            Layer::Field => self.pxl_vec[index] = self.brush.apply(self.pxl_vec[index], weight, erase),
+                            Layer::Mask => self.paint_mask(index, erase),
+                            Layer::Map(kind) => {
+                                let map = self.param_maps.get_mut(kind, self.pxl_vec.len());
+                                // Erasing a map goes back to the global value
+                                map[index] = if erase {
+                                    map[index] + (NEUTRAL - map[index]) * weight
+                                } else {
+                                    self.brush.apply(map[index], weight, false)
+                                };
+                            }
+                        }
                     }
                 }
             }
@@ -166,50 +423,54 @@ impl GameOfLife {
         self.height = new_height;
         self.a_width = new_width / self.pixel_edge_size;
         self.a_height = new_height / self.pixel_edge_size;
-        let new_a_size = (self.a_width * self.a_height) as usize;
 
-        self.pxl_vec = vec![0.0; new_a_size];
-        let mut rng = StdRng::seed_from_u64(42);
-        self.pxl_vec.iter_mut().for_each(|i| {
-            *i = rng.gen();
-        });
+        self.param_maps.clear_all();
+        self.mask = None;
+        self.rewind.clear();
+        self.tracker.clear();
+        self.pending_notes.clear();
+        self.periodicity = None;
+        if let Ok(mut synth) = self.synth.lock() {
+            synth.all_notes_off();
+        }
+        self.pxl_vec = self.init.generate(self.a_width, self.a_height);
+        self.rewind.push(self.generation, &self.pxl_vec);
+        self.fit_to_window();
+    }
+
+    // Window size changes only move the view; the field keeps its cells
+    pub fn set_view_size(&mut self, new_width: u32, new_height: u32) {
+        self.width = new_width;
+        self.height = new_height;
+        self.fit_to_window();
+    }
+
+    pub fn fit_to_window(&mut self) {
+        self.camera.fit(self.width, self.height, self.a_width, self.a_height);
     }
 
     pub fn change_pixel_size(&mut self, delta: i32) {
         let new_pixel_size = (self.pixel_edge_size as i32 + delta).clamp(1, 50) as u32;
         if new_pixel_size != self.pixel_edge_size {
+            self.record_history();
             self.pixel_edge_size = new_pixel_size;
             self.resize(self.width, self.height);
         }
     }
 
     pub fn change_parameter(&mut self, param: &str, delta: f64) {
-        match param {
-            "update_freq" => {
-                self.update_freq = (self.update_freq + delta).clamp(1.0, 100.0);
-            }
-            "kernel_rad" => {
-                self.kernel_rad = (self.kernel_rad as f64 + delta).clamp(1.0, 20.0) as u32;
-            }
-            "bell_m" => {
-                self.bell_m = (self.bell_m + delta).clamp(0.01, 1.0);
-            }
-            "bell_s" => {
-                self.bell_s = (self.bell_s + delta).clamp(0.01, 1.0);
-            }
-            "noise_intensity" => {
-                self.noise_intensity = (self.noise_intensity + delta).clamp(0.0, 1.0);
-            }
-            "info_bar_height" => {
-                self.info_bar_height = (self.info_bar_height as f64 + delta).clamp(0.0, 200.0) as u32;
-            }
-            _ => {}
+        if let Some(value) = self.get_parameter(param) {
+            self.record_history();
+            self.set_parameter(param, value + delta);
         }
     }
 
     pub fn reset_parameters(&mut self) {
+        self.record_history();
         self.pixel_edge_size = DEFAULT_PIXEL_EDGE_SIZE;
         self.update_freq = DEFAULT_UPDATE_FREQ;
+        self.dt = DEFAULT_DT;
+        self.integrator = Integrator::Euler;
         self.kernel_rad = DEFAULT_KERNEL_RAD;
         self.bell_m = DEFAULT_BELL_M;
         self.bell_s = DEFAULT_BELL_S;
@@ -229,7 +490,7 @@ impl GameOfLife {
             self.info_window = None;
         } else {
             let info_window = video_subsystem
-                .window("Simulation Info", 500, 400)
+                .window("Simulation Info", 500, 700)
                 .position_centered()
                 .build()
                 .unwrap();
@@ -239,6 +500,23 @@ impl GameOfLife {
         }
     }
 
+    pub fn cycle_integrator(&mut self) {
+        self.record_history();
+        self.integrator = self.integrator.cycle();
+    }
+
+    pub fn toggle_wrap(&mut self) {
+        self.record_history();
+        self.wrap_edges = !self.wrap_edges;
+    }
+
+    pub fn export_stats(&self) {
+        match self.stats.export_csv(STATS_CSV_PATH) {
+            Ok(()) => println!("Saved statistics to {}", STATS_CSV_PATH),
+            Err(e) => eprintln!("Failed to save statistics: {}", e),
+        }
+    }
+
     pub fn toggle_noise(&mut self) {
         self.noise_enabled = !self.noise_enabled;
     }
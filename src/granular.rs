@@ -0,0 +1,223 @@
+use std::f64::consts::{PI, TAU};
+use crate::game::GameOfLife;
+use crate::synth::SAMPLE_RATE;
+use crate::wav::WavData;
+
+pub const GRAIN_HEADS: usize = 4;
+pub const MAX_GRAINS: usize = 64;
+pub const DEFAULT_GRAIN_DENSITY: f64 = 40.0;
+pub const DEFAULT_GRAIN_DURATION: f64 = 0.08;
+pub const DEFAULT_GRAIN_PITCH: f64 = 0.3;
+pub const DEFAULT_GRAIN_SPEED: f64 = 0.5;
+pub const DEFAULT_GRAIN_GAIN: f64 = 0.5;
+const TAP_DISTANCE: f64 = 3.0; // Cells between a head and its look-ahead tap
+
+// What one read head asks of the audio side until the next step
+#[derive(Clone, Copy, Default)]
+pub struct GrainStream {
+    pub density: f64,  // Grains per second
+    pub position: f64, // 0..1 through the sample
+    pub rate: f64,     // Playback rate, 1 is the original pitch
+    pub duration: f64, // Seconds
+    pub pan: f64,
+}
+
+struct Grain {
+    position: f64, // In source samples
+    rate: f64,
+    length: usize, // In output samples
+    age: usize,
+    pan: f64,
+}
+
+// Plays Hann-windowed grains of a loaded sample, spawned at each stream's
+// density. Spawning is by accumulator rather than chance, so an offline
+// render is the same every time
+pub struct GrainEngine {
+    sample: Vec<f32>,
+    sample_rate: f64,
+    pub streams: Vec<GrainStream>,
+    pending: Vec<f64>, // Fractional grains owed per stream
+    grains: Vec<Grain>,
+    pub gain: f64,
+}
+
+impl GrainEngine {
+    pub fn new() -> Self {
+        Self {
+            sample: Vec::new(),
+            sample_rate: SAMPLE_RATE as f64,
+            streams: Vec::new(),
+            pending: Vec::new(),
+            grains: Vec::new(),
+            gain: DEFAULT_GRAIN_GAIN,
+        }
+    }
+
+    pub fn load(&mut self, wav: WavData) {
+        self.sample = wav.samples;
+        self.sample_rate = wav.sample_rate.max(1) as f64;
+        self.grains.clear();
+    }
+
+    pub fn has_sample(&self) -> bool {
+        !self.sample.is_empty()
+    }
+
+    pub fn active_grains(&self) -> usize {
+        self.grains.len()
+    }
+
+    fn spawn(&mut self, stream: GrainStream) {
+        if self.grains.len() >= MAX_GRAINS {
+            return;
+        }
+        self.grains.push(Grain {
+            position: stream.position.clamp(0.0, 1.0) * (self.sample.len() - 1) as f64,
+            rate: stream.rate * self.sample_rate / SAMPLE_RATE as f64,
+            length: ((stream.duration * SAMPLE_RATE as f64) as usize).max(2),
+            age: 0,
+            pan: stream.pan,
+        });
+    }
+
+    // Adds into an interleaved stereo buffer
+    pub fn render(&mut self, out: &mut [f32]) {
+        if !self.has_sample() {
+            return;
+        }
+        self.pending.resize(self.streams.len(), 0.0);
+        let dt = 1.0 / SAMPLE_RATE as f64;
+        let gain = self.gain / (MAX_GRAINS as f64).sqrt();
+
+        for frame in out.chunks_exact_mut(2) {
+            for i in 0..self.streams.len() {
+                self.pending[i] += self.streams[i].density * dt;
+                if self.pending[i] >= 1.0 {
+                    self.pending[i] -= 1.0;
+                    self.spawn(self.streams[i]);
+                }
+            }
+
+            let (mut left, mut right) = (0.0, 0.0);
+            for grain in &mut self.grains {
+                let i = grain.position as usize;
+                let t = grain.position - i as f64;
+                let a = self.sample.get(i).copied().unwrap_or(0.0) as f64;
+                let b = self.sample.get(i + 1).copied().unwrap_or(0.0) as f64;
+                let window = 0.5 - 0.5 * (TAU * grain.age as f64 / grain.length as f64).cos();
+                let sample = (a + (b - a) * t) * window * gain;
+                left += sample * (grain.pan * PI / 2.0).cos();
+                right += sample * (grain.pan * PI / 2.0).sin();
+                grain.position += grain.rate;
+                grain.age += 1;
+            }
+            self.grains.retain(|g| g.age < g.length);
+            frame[0] += left as f32;
+            frame[1] += right as f32;
+        }
+    }
+}
+
+// A point drifting across the field in a straight line, wrapping at the edges
+#[derive(Clone, Copy)]
+pub struct ReadHead {
+    pub x: f64,
+    pub y: f64,
+    pub angle: f64,
+}
+
+pub struct Granular {
+    pub enabled: bool,
+    pub heads: Vec<ReadHead>,
+    pub density: f64,  // Grains per second at a field value of 1
+    pub duration: f64, // Longest grain, in seconds
+    pub pitch: f64,    // 0 keeps the original pitch, 1 uses the quantiser's full range
+    pub speed: f64,    // Cells per step
+    pub gain: f64,
+}
+
+impl Granular {
+    // Heads start spread over the field, heading off at golden-angle turns
+    pub fn new(a_width: u32, a_height: u32) -> Self {
+        let golden = PI * (3.0 - 5f64.sqrt());
+        let heads = (0..GRAIN_HEADS)
+            .map(|i| {
+                let t = (i as f64 + 0.5) / GRAIN_HEADS as f64;
+                ReadHead { x: t * a_width as f64, y: (1.0 - t) * a_height as f64, angle: i as f64 * golden }
+            })
+            .collect();
+        Self {
+            enabled: false,
+            heads,
+            density: DEFAULT_GRAIN_DENSITY,
+            duration: DEFAULT_GRAIN_DURATION,
+            pitch: DEFAULT_GRAIN_PITCH,
+            speed: DEFAULT_GRAIN_SPEED,
+            gain: DEFAULT_GRAIN_GAIN,
+        }
+    }
+}
+
+impl GameOfLife {
+    fn cell_at(&self, x: f64, y: f64) -> f64 {
+        let (w, h) = (self.a_width as i64, self.a_height as i64);
+        let (x, y) = ((x.floor() as i64).rem_euclid(w), (y.floor() as i64).rem_euclid(h));
+        self.pxl_vec[(y * w + x) as usize]
+    }
+
+    // Move the heads and turn what they see into grain streams:
+    //   value under the head  -> position in the sample
+    //   mean of the 3x3 patch -> density
+    //   look-ahead tap        -> pitch, through the shared quantiser
+    //   patch contrast        -> duration, smooth regions give long grains
+    pub fn sonify_grains(&mut self) {
+        if !self.granular.enabled {
+            return;
+        }
+        let (w, h) = (self.a_width as f64, self.a_height as f64);
+        let speed = self.granular.speed;
+        for head in &mut self.granular.heads {
+            head.x = (head.x + speed * head.angle.cos()).rem_euclid(w);
+            head.y = (head.y + speed * head.angle.sin()).rem_euclid(h);
+        }
+
+        let centre_freq = self.quantiser.freq(0.5);
+        let streams: Vec<GrainStream> = self
+            .granular
+            .heads
+            .iter()
+            .map(|head| {
+                let patch: Vec<f64> = (-1..=1)
+                    .flat_map(|dy| (-1..=1).map(move |dx| (dx as f64, dy as f64)))
+                    .map(|(dx, dy)| self.cell_at(head.x + dx, head.y + dy))
+                    .collect();
+                let mean = patch.iter().sum::<f64>() / patch.len() as f64;
+                let contrast = patch.iter().fold(0.0, |m: f64, &v| m.max(v)) - patch.iter().fold(1.0, |m: f64, &v| m.min(v));
+                let ahead = self.cell_at(head.x + TAP_DISTANCE * head.angle.cos(), head.y + TAP_DISTANCE * head.angle.sin());
+                let pitch_value = 0.5 + (ahead - 0.5) * self.granular.pitch;
+                GrainStream {
+                    density: self.granular.density * mean,
+                    position: self.cell_at(head.x, head.y),
+                    rate: self.quantiser.freq(pitch_value) / centre_freq,
+                    duration: self.granular.duration * (1.0 - 0.9 * contrast.clamp(0.0, 1.0)),
+                    pan: head.x / w,
+                }
+            })
+            .collect();
+
+        if let Ok(mut synth) = self.synth.lock() {
+            synth.granular.streams = streams;
+            synth.granular.gain = self.granular.gain;
+        }
+    }
+
+    pub fn toggle_granular(&mut self) {
+        self.granular.enabled = !self.granular.enabled;
+        if !self.granular.enabled {
+            if let Ok(mut synth) = self.synth.lock() {
+                synth.granular.streams.clear();
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::fs;
+use crate::cli::{Options, DEFAULT_HEADLESS_SIZE, DEFAULT_HEADLESS_STEPS};
+use crate::game::GameOfLife;
+use crate::{search, sweep};
+use crate::synth::SAMPLE_RATE;
+use crate::wav::WavWriter;
+use crate::npy::NpyStack;
+
+// Run the simulation without a window: one generation per frame, with frames
+// and the synth output written to disk. Everything is seeded, so the same
+// options and input track always give the same result
+pub fn run(options: &Options) -> Result<(), String> {
+    if let Some((x_axis, y_axis)) = &options.sweep {
+        return sweep::run(options, x_axis, y_axis);
+    }
+    if let Some(iterations) = options.search {
+        return search::run(options, iterations);
+    }
+    let (a_width, a_height) = options.size.unwrap_or(DEFAULT_HEADLESS_SIZE);
+    let mut game = GameOfLife::new(a_width, a_height, 1);
+    game.apply_options(options)?;
+    game.running = true;
+
+    if let Some(dir) = &options.frames_out {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    }
+    let mut audio_out = match &options.audio_out {
+        Some(path) => Some(WavWriter::create(path, SAMPLE_RATE as u32).map_err(|e| format!("{}: {}", path, e))?),
+        None => None,
+    };
+
+    let mut npy_stack = match &options.npy_stack {
+        Some(path) => Some(NpyStack::create(path, game.a_width as usize, game.a_height as usize, options.npy_dtype()).map_err(|e| format!("{}: {}", path, e))?),
+        None => None,
+    };
+
+    // Carry the fractional sample count so long renders do not drift
+    let mut sample_clock = 0.0;
+    // Without an explicit length, render exactly the input track
+    let track_steps = game.modulation.audio.as_ref().map(|a| (a.duration / game.modulation.seconds_per_step).ceil() as u64);
+    let steps = options.steps.or(track_steps).unwrap_or(DEFAULT_HEADLESS_STEPS);
+    for frame in 0..steps {
+        game.step();
+
+        if let Some(dir) = &options.frames_out {
+            let path = format!("{}/frame_{:06}.ppm", dir, frame);
+            game.save_frame_ppm(&path).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        if let Some(stack) = npy_stack.as_mut() {
+            stack.push(&game.pxl_vec).map_err(|e| e.to_string())?;
+        }
+        if let Some(writer) = audio_out.as_mut() {
+            sample_clock += game.modulation.seconds_per_step * SAMPLE_RATE as f64;
+            let frames = sample_clock as usize;
+            sample_clock -= frames as f64;
+            let mut buffer = vec![0.0f32; frames * 2];
+            if let Ok(mut synth) = game.synth.lock() {
+                synth.render(&mut buffer);
+            }
+            writer.write(&buffer).map_err(|e| e.to_string())?;
+        }
+    }
+
+    if let Some(writer) = audio_out {
+        writer.finish().map_err(|e| e.to_string())?;
+    }
+    if let Some(stack) = npy_stack {
+        stack.finish().map_err(|e| e.to_string())?;
+    }
+    for (layer, path) in &options.npy_out {
+        game.save_npy(*layer, path, options.npy_dtype())?;
+    }
+    if let Some(path) = &options.field_out {
+        game.save_field_pgm(path)?;
+    }
+    println!("Ran {} generations", steps);
+    Ok(())
+}
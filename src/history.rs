@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+use crate::game::GameOfLife;
+use crate::integrate::Integrator;
+use crate::mask::CellType;
+use crate::param_map::ParamMaps;
+
+pub const DEFAULT_HISTORY_BYTES: usize = 64 * 1024 * 1024;
+const KEYFRAME_INTERVAL: usize = 16;
+
+#[derive(Clone)]
+pub struct ParamSnapshot {
+    pub pixel_edge_size: u32,
+    pub update_freq: f64,
+    pub dt: f64,
+    pub integrator: Integrator,
+    pub kernel_rad: u32,
+    pub bell_m: f64,
+    pub bell_s: f64,
+    pub noise_intensity: f64,
+    pub noise_enabled: bool,
+    pub wrap_edges: bool,
+}
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub field: Vec<f64>,
+    pub a_width: u32,
+    pub a_height: u32,
+    pub params: ParamSnapshot,
+    pub param_maps: ParamMaps,
+    pub mask: Option<Vec<CellType>>,
+}
+
+// A field stored as the run-length encoded XOR against the previous entry,
+// or against an all-zero field for keyframes
+struct Entry {
+    data: Vec<u8>,
+    keyframe: bool,
+    a_width: u32,
+    a_height: u32,
+    params: ParamSnapshot,
+    param_maps: ParamMaps, // Kept whole like the mask, they are rarely present and rarely change
+    mask: Option<Vec<CellType>>,
+}
+
+pub struct History {
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+    newest: Option<Vec<f64>>, // Decoded field of the newest undo entry
+    bytes: usize,
+    pub byte_cap: usize,
+}
+
+impl History {
+    pub fn new(byte_cap: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            newest: None,
+            bytes: 0,
+            byte_cap,
+        }
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot) {
+        self.bytes -= self.redo.drain(..).map(|e| e.bytes()).sum::<usize>();
+        self.push_undo(snapshot);
+    }
+
+    // Returns the state to restore, storing `current` so the step can be redone
+    pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let last = self.undo.len().checked_sub(1)?;
+        let field = self.decode(last);
+        let entry = self.undo.pop_back()?;
+        self.bytes -= entry.bytes();
+        self.newest = self.undo.len().checked_sub(1).map(|i| self.decode(i));
+
+        let redo = Entry::keyframe(current);
+        self.bytes += redo.bytes();
+        self.redo.push(redo);
+        self.evict();
+        Some(Snapshot {
+            field,
+            a_width: entry.a_width,
+            a_height: entry.a_height,
+            params: entry.params,
+            param_maps: entry.param_maps,
+            mask: entry.mask,
+        })
+    }
+
+    pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let entry = self.redo.pop()?;
+        self.bytes -= entry.bytes();
+        self.push_undo(current);
+        Some(Snapshot {
+            field: xor_decode(&entry.data, None, (entry.a_width * entry.a_height) as usize),
+            a_width: entry.a_width,
+            a_height: entry.a_height,
+            params: entry.params,
+            param_maps: entry.param_maps,
+            mask: entry.mask,
+        })
+    }
+
+    fn push_undo(&mut self, snapshot: Snapshot) {
+        let since_keyframe = self.undo.iter().rev().take_while(|e| !e.keyframe).count();
+        let base = match (&self.newest, self.undo.back()) {
+            (Some(prev), Some(back))
+                if back.a_width == snapshot.a_width
+                    && back.a_height == snapshot.a_height
+                    && since_keyframe + 1 < KEYFRAME_INTERVAL =>
+            {
+                Some(prev.as_slice())
+            }
+            _ => None,
+        };
+
+        let entry = Entry {
+            data: xor_encode(&snapshot.field, base),
+            keyframe: base.is_none(),
+            a_width: snapshot.a_width,
+            a_height: snapshot.a_height,
+            params: snapshot.params,
+            param_maps: snapshot.param_maps,
+            mask: snapshot.mask,
+        };
+        self.bytes += entry.bytes();
+        self.undo.push_back(entry);
+        self.newest = Some(snapshot.field);
+        self.evict();
+    }
+
+    // Redo entries are whole keyframes and count against the cap as well.
+    // Whichever stack is longer loses the step furthest from the present:
+    // the last redo, or the oldest undo with the next one made a keyframe
+    fn evict(&mut self) {
+        while self.bytes > self.byte_cap {
+            if self.redo.len() > self.undo.len().max(1) {
+                let furthest = self.redo.remove(0);
+                self.bytes -= furthest.bytes();
+            } else if self.undo.len() > 1 {
+                if !self.undo[1].keyframe {
+                    let field = self.decode(1);
+                    let next = &mut self.undo[1];
+                    self.bytes -= next.data.len();
+                    next.data = xor_encode(&field, None);
+                    next.keyframe = true;
+                    self.bytes += next.data.len();
+                }
+                if let Some(oldest) = self.undo.pop_front() {
+                    self.bytes -= oldest.bytes();
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn decode(&self, index: usize) -> Vec<f64> {
+        let start = (0..=index).rev().find(|&i| self.undo[i].keyframe).unwrap_or(0);
+        let mut field: Option<Vec<f64>> = None;
+        for entry in self.undo.range(start..=index) {
+            let len = (entry.a_width * entry.a_height) as usize;
+            field = Some(xor_decode(&entry.data, field.as_deref(), len));
+        }
+        field.unwrap_or_default()
+    }
+}
+
+impl Entry {
+    fn keyframe(snapshot: Snapshot) -> Self {
+        Self {
+            data: xor_encode(&snapshot.field, None),
+            keyframe: true,
+            a_width: snapshot.a_width,
+            a_height: snapshot.a_height,
+            params: snapshot.params,
+            param_maps: snapshot.param_maps,
+            mask: snapshot.mask,
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        self.data.len() + self.param_maps.bytes() + self.mask.as_ref().map_or(0, |m| m.len() * size_of::<CellType>())
+    }
+}
+
+// Unchanged cells XOR to zero words, which collapse into a single run length
+fn xor_encode(field: &[f64], base: Option<&[f64]>) -> Vec<u8> {
+    let words: Vec<u64> = field
+        .iter()
+        .enumerate()
+        .map(|(i, v)| v.to_bits() ^ base.map_or(0, |b| b[i].to_bits()))
+        .collect();
+
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let zeros = words[i..].iter().take_while(|&&w| w == 0).count();
+        i += zeros;
+        let literals = words[i..].iter().take_while(|&&w| w != 0).count();
+        write_varint(&mut out, zeros as u64);
+        write_varint(&mut out, literals as u64);
+        for w in &words[i..i + literals] {
+            out.extend_from_slice(&w.to_le_bytes());
+        }
+        i += literals;
+    }
+    out
+}
+
+fn xor_decode(data: &[u8], base: Option<&[f64]>, len: usize) -> Vec<f64> {
+    let mut words = vec![0u64; len];
+    let mut pos = 0;
+    let mut i = 0;
+    while pos < data.len() {
+        i += read_varint(data, &mut pos) as usize;
+        let literals = read_varint(data, &mut pos) as usize;
+        for _ in 0..literals {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[pos..pos + 8]);
+            words[i] = u64::from_le_bytes(bytes);
+            pos += 8;
+            i += 1;
+        }
+    }
+    words
+        .iter()
+        .enumerate()
+        .map(|(i, w)| f64::from_bits(w ^ base.map_or(0, |b| b[i].to_bits())))
+        .collect()
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    while *pos < data.len() {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+impl GameOfLife {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            field: self.pxl_vec.clone(),
+            a_width: self.a_width,
+            a_height: self.a_height,
+            params: ParamSnapshot {
+                pixel_edge_size: self.pixel_edge_size,
+                update_freq: self.update_freq,
+                dt: self.dt,
+                integrator: self.integrator,
+                kernel_rad: self.kernel_rad,
+                bell_m: self.bell_m,
+                bell_s: self.bell_s,
+                noise_intensity: self.noise_intensity,
+                noise_enabled: self.noise_enabled,
+                wrap_edges: self.wrap_edges,
+            },
+            param_maps: self.param_maps.clone(),
+            mask: self.mask.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        let resized = snapshot.a_width != self.a_width || snapshot.a_height != self.a_height;
+        self.pxl_vec = snapshot.field;
+        self.a_width = snapshot.a_width;
+        self.a_height = snapshot.a_height;
+        self.pixel_edge_size = snapshot.params.pixel_edge_size;
+        self.update_freq = snapshot.params.update_freq;
+        self.dt = snapshot.params.dt;
+        self.integrator = snapshot.params.integrator;
+        self.kernel_rad = snapshot.params.kernel_rad;
+        self.bell_m = snapshot.params.bell_m;
+        self.bell_s = snapshot.params.bell_s;
+        self.noise_intensity = snapshot.params.noise_intensity;
+        self.noise_enabled = snapshot.params.noise_enabled;
+        self.wrap_edges = snapshot.params.wrap_edges;
+        self.param_maps = snapshot.param_maps;
+        self.mask = snapshot.mask;
+        if resized {
+            self.rewind.clear();
+            self.tracker.clear();
+            self.pending_notes.clear();
+            self.periodicity = None;
+            self.rewind.push(self.generation, &self.pxl_vec);
+            self.fit_to_window();
+        }
+    }
+
+    // Call before any edit that should be undoable
+    pub fn record_history(&mut self) {
+        let snapshot = self.snapshot();
+        self.history.push(snapshot);
+    }
+
+    pub fn undo(&mut self) {
+        let current = self.snapshot();
+        if let Some(snapshot) = self.history.undo(current) {
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        let current = self.snapshot();
+        if let Some(snapshot) = self.history.redo(current) {
+            self.restore(snapshot);
+        }
+    }
+}
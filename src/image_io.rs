@@ -0,0 +1,512 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use crate::game::GameOfLife;
+use crate::param_map::MapKind;
+
+pub const FIELD_IMAGE_PATH: &str = "field.pgm";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+pub fn write_ppm(path: &str, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    out.write_all(rgb)?;
+    out.flush()
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut body = kind.to_vec();
+    body.extend_from_slice(data);
+    out.write_all(&body)?;
+    out.write_all(&crc32(&body).to_be_bytes())
+}
+
+// 8-bit RGB PNG. The image data goes into stored (uncompressed) deflate
+// blocks, which every decoder reads and needs no compression library
+pub fn write_png(path: &str, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(PNG_SIGNATURE)?;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]); // Depth, RGB, deflate, no filter, no interlace
+    write_chunk(&mut out, b"IHDR", &header)?;
+
+    // Every row starts with filter type 0
+    let raw: Vec<u8> = rgb.chunks(width as usize * 3).flat_map(|row| std::iter::once(0).chain(row.iter().copied())).collect();
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(65535).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push((i + 1 == blocks.len()) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    if blocks.is_empty() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+    write_chunk(&mut out, b"IDAT", &zlib)?;
+    write_chunk(&mut out, b"IEND", &[])?;
+    out.flush()
+}
+
+// Next whitespace-separated header token of a PNM file; '#' starts a comment
+fn pnm_token(data: &[u8], pos: &mut usize) -> Option<String> {
+    while *pos < data.len() {
+        match data[*pos] {
+            b'#' => while *pos < data.len() && data[*pos] != b'\n' { *pos += 1 },
+            c if c.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+    let start = *pos;
+    while *pos < data.len() && !data[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    (start < *pos).then(|| String::from_utf8_lossy(&data[start..*pos]).into_owned())
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImageChannel {
+    Luma,
+    Red,
+    Green,
+    Blue,
+}
+
+impl ImageChannel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "luma" => Some(ImageChannel::Luma),
+            "red" => Some(ImageChannel::Red),
+            "green" => Some(ImageChannel::Green),
+            "blue" => Some(ImageChannel::Blue),
+            _ => None,
+        }
+    }
+}
+
+// How an image becomes cells: one channel into the field, or red into the
+// field with green and blue into the m and s maps
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImageMode {
+    Channel(ImageChannel),
+    Rgb,
+}
+
+impl ImageMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name == "rgb" {
+            Some(ImageMode::Rgb)
+        } else {
+            ImageChannel::from_name(name).map(ImageMode::Channel)
+        }
+    }
+}
+
+// A decoded image with colour values in [0, 1]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<[f64; 3]>,
+}
+
+impl Image {
+    pub fn channel(&self, channel: ImageChannel) -> Vec<f64> {
+        self.rgb
+            .iter()
+            .map(|&[r, g, b]| match channel {
+                ImageChannel::Luma => 0.299 * r + 0.587 * g + 0.114 * b,
+                ImageChannel::Red => r,
+                ImageChannel::Green => g,
+                ImageChannel::Blue => b,
+            })
+            .collect()
+    }
+}
+
+// PNG, PGM (P2/P5) or PPM (P3/P6), told apart by their first bytes
+pub fn read_image(path: &str) -> Result<Image, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let image = if data.starts_with(PNG_SIGNATURE) { decode_png(&data) } else { decode_pnm(&data) };
+    let image = image.map_err(|e| format!("{}: {}", path, e))?;
+    if image.width == 0 || image.height == 0 {
+        return Err(format!("{}: empty image", path));
+    }
+    Ok(image)
+}
+
+// Gray levels of any image read_image takes
+pub fn read_gray(path: &str) -> Result<(usize, usize, Vec<f64>), String> {
+    let image = read_image(path)?;
+    Ok((image.width, image.height, image.channel(ImageChannel::Luma)))
+}
+
+fn decode_pnm(data: &[u8]) -> Result<Image, String> {
+    let mut pos = 0;
+    let magic = pnm_token(data, &mut pos).ok_or("empty file")?;
+    let channels = match magic.as_str() {
+        "P2" | "P5" => 1,
+        "P3" | "P6" => 3,
+        _ => return Err("not a PNG, PGM or PPM file".to_string()),
+    };
+    let mut number = || pnm_token(data, &mut pos).and_then(|t| t.parse::<usize>().ok()).ok_or("bad header");
+    let (width, height, max) = (number()?, number()?, number()?);
+    if max == 0 || max > 65535 {
+        return Err("bad maximum value".to_string());
+    }
+    let count = width * height * channels;
+    let samples: Vec<f64> = if magic == "P2" || magic == "P3" {
+        let samples: Option<Vec<f64>> = (0..count).map(|_| pnm_token(data, &mut pos).and_then(|t| t.parse::<f64>().ok())).collect();
+        samples.ok_or("too few samples")?
+    } else {
+        // A single whitespace byte separates the header from the samples
+        let body = data.get(pos + 1..).unwrap_or(&[]);
+        let bytes = if max > 255 { 2 } else { 1 };
+        if body.len() < count * bytes {
+            return Err("too few samples".to_string());
+        }
+        body.chunks(bytes).take(count).map(|b| if bytes == 2 { u16::from_be_bytes([b[0], b[1]]) as f64 } else { b[0] as f64 }).collect()
+    };
+    let rgb = samples
+        .chunks(channels)
+        .map(|c| {
+            let v = |i: usize| (c[i.min(channels - 1)] / max as f64).clamp(0.0, 1.0);
+            [v(0), v(1), v(2)]
+        })
+        .collect();
+    Ok(Image { width, height, rgb })
+}
+
+// Least significant bit first, as deflate packs them
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize, // In bits
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos / 8).ok_or("deflate data ends early")?;
+            value |= (((byte >> (self.pos % 8)) & 1) as u32) << i;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+}
+
+// Canonical Huffman code as symbol counts per length and symbols in code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        lengths.iter().for_each(|&l| counts[l as usize] += 1);
+        counts[0] = 0;
+        let mut symbols: Vec<(u8, u16)> = lengths.iter().enumerate().filter(|(_, &l)| l > 0).map(|(s, &l)| (l, s as u16)).collect();
+        symbols.sort();
+        Self { counts, symbols: symbols.into_iter().map(|(_, s)| s).collect() }
+    }
+
+    // Codes are read a bit at a time, most significant first; the codes of
+    // one length are consecutive numbers starting at `first`
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("bad Huffman code".to_string())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// The zlib stream of a PNG. The checksum is not verified; the PNG chunk
+// CRCs and the decoded size catch damaged files
+fn inflate(zlib: &[u8]) -> Result<Vec<u8>, String> {
+    if zlib.len() < 2 || zlib[0] & 0x0F != 8 {
+        return Err("not a deflate stream".to_string());
+    }
+    let mut reader = BitReader { data: zlib, pos: 16 };
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                let start = reader.pos.div_ceil(8);
+                let block = zlib.get(start..start + 4).ok_or("deflate data ends early")?;
+                let len = u16::from_le_bytes([block[0], block[1]]) as usize;
+                out.extend_from_slice(zlib.get(start + 4..start + 4 + len).ok_or("deflate data ends early")?);
+                reader.pos = (start + 4 + len) * 8;
+            }
+            kind @ (1 | 2) => {
+                let (lit, dist) = if kind == 1 {
+                    let mut lengths = [8u8; 288];
+                    lengths[144..256].fill(9);
+                    lengths[256..280].fill(7);
+                    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+                } else {
+                    let literals = reader.bits(5)? as usize + 257;
+                    let distances = reader.bits(5)? as usize + 1;
+                    let code_lengths = reader.bits(4)? as usize + 4;
+                    let mut order = [0u8; 19];
+                    for &i in &CODE_LENGTH_ORDER[..code_lengths] {
+                        order[i] = reader.bits(3)? as u8;
+                    }
+                    let code = Huffman::new(&order);
+                    let mut lengths = Vec::with_capacity(literals + distances);
+                    while lengths.len() < literals + distances {
+                        let (value, repeat) = match code.decode(&mut reader)? {
+                            16 => (*lengths.last().ok_or("repeat with no length")?, reader.bits(2)? + 3),
+                            17 => (0, reader.bits(3)? + 3),
+                            18 => (0, reader.bits(7)? + 11),
+                            symbol => (symbol as u8, 1),
+                        };
+                        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+                    }
+                    if lengths.len() > literals + distances {
+                        return Err("code lengths overrun".to_string());
+                    }
+                    (Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..]))
+                };
+                loop {
+                    let symbol = lit.decode(&mut reader)? as usize;
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    let i = symbol - 257;
+                    if i >= LENGTH_BASE.len() {
+                        return Err("bad length code".to_string());
+                    }
+                    let len = LENGTH_BASE[i] as usize + reader.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                    let d = dist.decode(&mut reader)? as usize;
+                    if d >= DIST_BASE.len() {
+                        return Err("bad distance code".to_string());
+                    }
+                    let distance = DIST_BASE[d] as usize + reader.bits(DIST_EXTRA[d] as u32)? as usize;
+                    let from = out.len().checked_sub(distance).ok_or("distance before start")?;
+                    // Byte by byte, since the copy may overlap what it writes
+                    for k in 0..len {
+                        out.push(out[from + k]);
+                    }
+                }
+            }
+            _ => return Err("bad deflate block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+// Non-interlaced PNGs of every colour type and bit depth. Alpha is applied
+// against black, so transparent areas load as empty cells
+fn decode_png(data: &[u8]) -> Result<Image, String> {
+    let mut pos = PNG_SIGNATURE.len();
+    let (mut header, mut palette, mut zlib) = (None, Vec::new(), Vec::new());
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let body = data.get(pos + 4..pos + 8 + len).ok_or("chunk runs past the end")?;
+        let crc = data.get(pos + 8 + len..pos + 12 + len).ok_or("chunk runs past the end")?;
+        if crc32(body).to_be_bytes() != crc {
+            return Err("chunk checksum mismatch".to_string());
+        }
+        let (kind, content) = body.split_at(4);
+        match kind {
+            b"IHDR" if content.len() >= 13 => header = Some(content.to_vec()),
+            b"PLTE" if content.len() % 3 != 0 => return Err("palette length is not a multiple of 3".to_string()),
+            b"PLTE" => palette = content.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+            b"IDAT" => zlib.extend_from_slice(content),
+            b"IEND" => break,
+            _ => {}
+        }
+        pos += 12 + len;
+    }
+    let header = header.ok_or("no IHDR chunk")?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let (depth, color) = (header[8] as usize, header[9]);
+    if header[12] != 0 {
+        return Err("interlaced PNGs are not supported".to_string());
+    }
+    let channels = match color {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(format!("bad colour type {}", color)),
+    };
+    if ![1, 2, 4, 8, 16].contains(&depth) {
+        return Err(format!("bad bit depth {}", depth));
+    }
+
+    let raw = inflate(&zlib)?;
+    // Sizes come straight from IHDR, so a product too big to address rejects the image
+    let stride = width.checked_mul(channels * depth).ok_or("image too large")?.div_ceil(8);
+    let bpp = (channels * depth).div_ceil(8); // Filters look this many bytes back
+    if raw.len() < (stride + 1).checked_mul(height).ok_or("image too large")? {
+        return Err("image data too short".to_string());
+    }
+    let mut pixels = vec![0u8; stride * height];
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (done, rest) = pixels.split_at_mut(y * stride);
+        let prior = if y > 0 { &done[(y - 1) * stride..] } else { &[][..] };
+        let row = &mut rest[..stride];
+        for i in 0..stride {
+            let a = if i >= bpp { row[i - bpp] as i16 } else { 0 };
+            let b = prior.get(i).copied().unwrap_or(0) as i16;
+            let c = if i >= bpp { prior.get(i - bpp).copied().unwrap_or(0) as i16 } else { 0 };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => (a + b) / 2,
+                4 => {
+                    let p = a + b - c;
+                    let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+                    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+                }
+                _ => return Err(format!("bad filter type {}", filter)),
+            };
+            row[i] = line[i].wrapping_add(predicted as u8);
+        }
+    }
+
+    let max = ((1u32 << depth) - 1) as f64;
+    let sample = |row: &[u8], i: usize| -> u32 {
+        match depth {
+            16 => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]) as u32,
+            8 => row[i] as u32,
+            _ => {
+                let bit = i * depth;
+                ((row[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1) as u8) as u32
+            }
+        }
+    };
+    let mut rgb = Vec::with_capacity(width * height);
+    for row in pixels.chunks(stride) {
+        for x in 0..width {
+            let v = |c: usize| sample(row, x * channels + c) as f64 / max;
+            rgb.push(match color {
+                0 => [v(0); 3],
+                2 => [v(0), v(1), v(2)],
+                3 => {
+                    let entry = palette.get(sample(row, x) as usize).ok_or("palette index out of range")?;
+                    entry.map(|c| c as f64 / 255.0)
+                }
+                4 => [v(0) * v(1); 3],
+                _ => [v(0) * v(3), v(1) * v(3), v(2) * v(3)],
+            });
+        }
+    }
+    Ok(Image { width, height, rgb })
+}
+
+// 16-bit binary PGM of values in [0, 1], for fields without 8-bit rounding
+pub fn write_pgm16(path: &str, width: u32, height: u32, values: &[f64]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "P5\n{} {}\n65535\n", width, height)?;
+    for &v in values {
+        out.write_all(&((v.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes())?;
+    }
+    out.flush()
+}
+
+// Bilinear resampling of a width x height grid to new_width x new_height
+pub fn resample(values: &[f64], width: usize, height: usize, new_width: usize, new_height: usize) -> Vec<f64> {
+    let at = |x: usize, y: usize| values[y.min(height - 1) * width + x.min(width - 1)];
+    (0..new_width * new_height)
+        .map(|i| {
+            // Sample at cell centres so the image is neither shifted nor cropped
+            let x = (((i % new_width) as f64 + 0.5) * width as f64 / new_width as f64 - 0.5).max(0.0);
+            let y = (((i / new_width) as f64 + 0.5) * height as f64 / new_height as f64 - 0.5).max(0.0);
+            let (x0, y0) = (x as usize, y as usize);
+            let (tx, ty) = (x - x0 as f64, y - y0 as f64);
+            let top = at(x0, y0) * (1.0 - tx) + at(x0 + 1, y0) * tx;
+            let bottom = at(x0, y0 + 1) * (1.0 - tx) + at(x0 + 1, y0 + 1) * tx;
+            top * (1.0 - ty) + bottom * ty
+        })
+        .collect()
+}
+
+impl GameOfLife {
+    // The field at one pixel per cell, colored with the current gradient
+    pub fn field_rgb(&self) -> Vec<u8> {
+        self.pxl_vec
+            .iter()
+            .flat_map(|&val| {
+                let color = self.colors[(val * 255.0).clamp(0.0, 255.0) as usize];
+                [color.r, color.g, color.b]
+            })
+            .collect()
+    }
+
+    pub fn save_frame_ppm(&self, path: &str) -> io::Result<()> {
+        write_ppm(path, self.a_width, self.a_height, &self.field_rgb())
+    }
+
+    pub fn save_field_pgm(&self, path: &str) -> Result<(), String> {
+        write_pgm16(path, self.a_width, self.a_height, &self.pxl_vec).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Replace the field with an image stretched over it
+    pub fn load_field_image(&mut self, path: &str, mode: ImageMode) -> Result<(), String> {
+        let image = read_image(path)?;
+        let (a_width, a_height) = (self.a_width as usize, self.a_height as usize);
+        let fit = |channel| resample(&image.channel(channel), image.width, image.height, a_width, a_height);
+        self.record_history();
+        match mode {
+            ImageMode::Channel(channel) => self.pxl_vec = fit(channel),
+            ImageMode::Rgb => {
+                self.pxl_vec = fit(ImageChannel::Red);
+                self.param_maps.set(MapKind::M, fit(ImageChannel::Green));
+                self.param_maps.set(MapKind::S, fit(ImageChannel::Blue));
+            }
+        }
+        self.restart_from_field();
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use rand::{Rng, rngs::StdRng, SeedableRng};
+use crate::game::{GameOfLife, DEFAULT_SEED};
+use crate::image_io::{read_gray, resample};
+use crate::noise::perlin_field;
+use crate::pattern::{stamp, Pattern};
+use crate::utils::bell;
+
+const DEFAULT_COUNT: usize = 12;
+const DEFAULT_SIZE: usize = 16;
+const DEFAULT_RADIUS: f64 = 10.0;
+const DEFAULT_SCALE: f64 = 16.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InitKind {
+    Uniform,  // Every cell random
+    Empty,
+    Patches,  // Squares of random cells
+    Blobs,    // Gaussian bumps
+    Rings,
+    Perlin,
+    Image,    // A grayscale image stretched over the field
+    Scatter,  // Copies of a pattern
+}
+
+impl InitKind {
+    pub const ALL: [InitKind; 8] = [
+        InitKind::Uniform,
+        InitKind::Empty,
+        InitKind::Patches,
+        InitKind::Blobs,
+        InitKind::Rings,
+        InitKind::Perlin,
+        InitKind::Image,
+        InitKind::Scatter,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InitKind::Uniform => "uniform",
+            InitKind::Empty => "empty",
+            InitKind::Patches => "patches",
+            InitKind::Blobs => "blobs",
+            InitKind::Rings => "rings",
+            InitKind::Perlin => "perlin",
+            InitKind::Image => "image",
+            InitKind::Scatter => "scatter",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.name() == name)
+    }
+}
+
+// How new and reset worlds are filled. Written like noise models,
+// "kind:key=value:...", e.g. "patches:count=20:size=8:seed=3" or
+// "scatter:pattern=orbium.pattern:count=6". Files are read when the spec is
+// parsed, and switching kind keeps them for later
+#[derive(Clone)]
+pub struct InitGenerator {
+    pub kind: InitKind,
+    pub seed: u64,
+    pub count: usize,  // Patches, blobs, rings and scattered stamps
+    pub size: usize,   // Patch edge in cells
+    pub radius: f64,   // Blob and ring radius in cells
+    pub scale: f64,    // Perlin lattice square in cells
+    pub image: Option<(String, usize, usize, Vec<f64>)>, // Path, width, height and gray levels
+    pub pattern: Option<(String, Pattern)>,
+}
+
+impl InitGenerator {
+    pub fn new(kind: InitKind) -> Self {
+        Self {
+            kind,
+            seed: DEFAULT_SEED,
+            count: DEFAULT_COUNT,
+            size: DEFAULT_SIZE,
+            radius: DEFAULT_RADIUS,
+            scale: DEFAULT_SCALE,
+            image: None,
+            pattern: None,
+        }
+    }
+
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.split(':');
+        let name = parts.next().unwrap_or("");
+        let mut init = Self::new(InitKind::from_name(name).ok_or(format!("unknown initial condition '{}'", name))?);
+        for part in parts {
+            let (key, value) = part.split_once('=').ok_or(format!("initial condition setting '{}' is not key=value", part))?;
+            let positive = || match value.parse::<f64>() {
+                Ok(v) if v > 0.0 => Ok(v),
+                _ => Err(format!("{} needs a positive number", key)),
+            };
+            match key {
+                "seed" => init.seed = value.parse().map_err(|_| format!("bad seed '{}'", value))?,
+                "count" => init.count = value.parse().map_err(|_| format!("bad count '{}'", value))?,
+                "size" => init.size = positive()? as usize,
+                "radius" => init.radius = positive()?,
+                "scale" => init.scale = positive()?,
+                "path" => {
+                    let (width, height, gray) = read_gray(value)?;
+                    init.image = Some((value.to_string(), width, height, gray));
+                }
+                "pattern" => init.pattern = Some((value.to_string(), Pattern::load(value)?)),
+                _ => return Err(format!("unknown initial condition setting '{}'", key)),
+            }
+        }
+        if init.kind == InitKind::Image && init.image.is_none() {
+            return Err("image needs path=FILE".to_string());
+        }
+        if init.kind == InitKind::Scatter && init.pattern.is_none() {
+            return Err("scatter needs pattern=FILE".to_string());
+        }
+        Ok(init)
+    }
+
+    pub fn spec(&self) -> String {
+        let mut spec = self.kind.name().to_string();
+        match self.kind {
+            InitKind::Uniform | InitKind::Empty => {}
+            InitKind::Patches => spec += &format!(":count={}:size={}", self.count, self.size),
+            InitKind::Blobs | InitKind::Rings => spec += &format!(":count={}:radius={}", self.count, self.radius),
+            InitKind::Perlin => spec += &format!(":scale={}", self.scale),
+            InitKind::Image => {
+                if let Some((path, ..)) = &self.image {
+                    spec += &format!(":path={}", path);
+                }
+            }
+            InitKind::Scatter => {
+                spec += &format!(":count={}", self.count);
+                if let Some((path, _)) = &self.pattern {
+                    spec += &format!(":pattern={}", path);
+                }
+            }
+        }
+        if self.kind != InitKind::Empty {
+            spec += &format!(":seed={}", self.seed);
+        }
+        spec
+    }
+
+    // A field of width x height cells. Image and scatter without their file
+    // give an empty field
+    pub fn generate(&self, width: u32, height: u32) -> Vec<f64> {
+        let (w, h) = (width as usize, height as usize);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut field = vec![0.0; w * h];
+        let place = |rng: &mut StdRng| (rng.gen_range(0..width as i32), rng.gen_range(0..height as i32));
+        match self.kind {
+            InitKind::Uniform => field.iter_mut().for_each(|v| *v = rng.gen()),
+            InitKind::Empty => {}
+            InitKind::Patches => {
+                for _ in 0..self.count {
+                    let (x0, y0) = place(&mut rng);
+                    for i in 0..self.size * self.size {
+                        let x = (x0 + (i % self.size) as i32).rem_euclid(width as i32) as usize;
+                        let y = (y0 + (i / self.size) as i32).rem_euclid(height as i32) as usize;
+                        field[y * w + x] = rng.gen();
+                    }
+                }
+            }
+            InitKind::Blobs | InitKind::Rings => {
+                let reach = (self.radius * 2.0).ceil() as i32;
+                for _ in 0..self.count {
+                    let (cx, cy) = place(&mut rng);
+                    for dy in -reach..=reach {
+                        for dx in -reach..=reach {
+                            let d = ((dx * dx + dy * dy) as f64).sqrt();
+                            let value = if self.kind == InitKind::Blobs {
+                                bell(d, 0.0, self.radius / 2.0)
+                            } else {
+                                bell(d, self.radius, self.radius / 6.0)
+                            };
+                            let x = (cx + dx).rem_euclid(width as i32) as usize;
+                            let y = (cy + dy).rem_euclid(height as i32) as usize;
+                            // Overlaps keep the higher value instead of saturating
+                            field[y * w + x] = f64::max(field[y * w + x], value);
+                        }
+                    }
+                }
+            }
+            InitKind::Perlin => {
+                field = perlin_field(width, height, self.scale, self.seed).into_iter().map(|n| (0.5 + 0.5 * n).clamp(0.0, 1.0)).collect();
+            }
+            InitKind::Image => {
+                if let Some((_, iw, ih, gray)) = &self.image {
+                    field = resample(gray, *iw, *ih, w, h);
+                }
+            }
+            InitKind::Scatter => {
+                if let Some((_, pattern)) = &self.pattern {
+                    for _ in 0..self.count {
+                        let (cx, cy) = place(&mut rng);
+                        stamp(&mut field, width, height, pattern, cx, cy);
+                    }
+                }
+            }
+        }
+        field
+    }
+
+    // One menu line: the kinds by number, the current one in brackets
+    pub fn menu(&self) -> String {
+        InitKind::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, &k)| if k == self.kind { format!("[{} {}]", i + 1, k.name()) } else { format!("{} {}", i + 1, k.name()) })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl GameOfLife {
+    // Number keys: pick the generator that new and reset worlds use
+    pub fn select_init(&mut self, index: usize) {
+        if let Some(&kind) = InitKind::ALL.get(index) {
+            self.init.kind = kind;
+        }
+    }
+
+    // Backspace: a new world from the current generator, keeping the parameters
+    pub fn new_world(&mut self) {
+        self.record_history();
+        self.pxl_vec = self.init.generate(self.a_width, self.a_height);
+        self.restart_from_field();
+    }
+}
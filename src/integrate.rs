@@ -0,0 +1,66 @@
+use rayon::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Integrator {
+    Euler,
+    Midpoint,
+    Rk4,
+}
+
+impl Integrator {
+    pub fn cycle(self) -> Self {
+        match self {
+            Integrator::Euler => Integrator::Midpoint,
+            Integrator::Midpoint => Integrator::Rk4,
+            Integrator::Rk4 => Integrator::Euler,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Euler" => Some(Integrator::Euler),
+            "Midpoint" => Some(Integrator::Midpoint),
+            "Rk4" => Some(Integrator::Rk4),
+            _ => None,
+        }
+    }
+
+    // One step of dA/dt = rate(A). Intermediate states are clamped to [0, 1]
+    // like the field itself so the growth function sees valid values; the
+    // result is left unclamped for the caller
+    pub fn integrate<F>(self, field: &[f64], dt: f64, mut rate: F) -> Vec<f64>
+    where
+        F: FnMut(&[f64]) -> Vec<f64>,
+    {
+        let k1 = rate(field);
+        match self {
+            Integrator::Euler => advance(field, &k1, dt),
+            Integrator::Midpoint => {
+                let k2 = rate(&offset(field, &k1, dt / 2.0));
+                advance(field, &k2, dt)
+            }
+            Integrator::Rk4 => {
+                let k2 = rate(&offset(field, &k1, dt / 2.0));
+                let k3 = rate(&offset(field, &k2, dt / 2.0));
+                let k4 = rate(&offset(field, &k3, dt));
+                field
+                    .par_iter()
+                    .enumerate()
+                    .map(|(i, a)| a + dt / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]))
+                    .collect()
+            }
+        }
+    }
+}
+
+fn advance(field: &[f64], k: &[f64], h: f64) -> Vec<f64> {
+    field.par_iter().zip(k.par_iter()).map(|(a, k)| a + h * k).collect()
+}
+
+fn offset(field: &[f64], k: &[f64], h: f64) -> Vec<f64> {
+    field
+        .par_iter()
+        .zip(k.par_iter())
+        .map(|(a, k)| (a + h * k).clamp(0.0, 1.0))
+        .collect()
+}
@@ -0,0 +1,215 @@
+use std::fs;
+use rand::{Rng, rngs::StdRng, SeedableRng};
+use sdl2::gfx::primitives::DrawRenderer;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::game::{GameOfLife, DEFAULT_SEED};
+use crate::pattern::{Pattern, PATTERN_EXTENSION};
+use crate::utils::bell;
+
+pub const DEFAULT_PATTERN_DIR: &str = "patterns";
+const THUMB_SIZE: i32 = 64;
+const PANEL_MARGIN: i32 = 8;
+const ROW_HEIGHT: i32 = THUMB_SIZE + 20; // Thumbnail plus its name
+const PANEL_WIDTH: i32 = THUMB_SIZE + 2 * PANEL_MARGIN;
+const BUILT_IN_SIZE: usize = 24;
+const MIN_SCALE: f64 = 0.25;
+const MAX_SCALE: f64 = 8.0;
+
+// A pattern made from a function of the offset from its centre
+fn shape(name: &str, comment: &str, mut value: impl FnMut(f64, f64) -> f64) -> Pattern {
+    let c = (BUILT_IN_SIZE as f64 - 1.0) / 2.0;
+    let cells = (0..BUILT_IN_SIZE * BUILT_IN_SIZE)
+        .map(|i| value((i % BUILT_IN_SIZE) as f64 - c, (i / BUILT_IN_SIZE) as f64 - c).clamp(0.0, 1.0))
+        .collect();
+    Pattern {
+        name: name.to_string(),
+        comments: vec![comment.to_string()],
+        params: Vec::new(),
+        width: BUILT_IN_SIZE,
+        height: BUILT_IN_SIZE,
+        cells,
+    }
+}
+
+// Starting shapes that need no files. They carry no parameters, so they
+// grow under whatever the world is set to
+fn built_in() -> Vec<Pattern> {
+    let r = BUILT_IN_SIZE as f64 / 2.0 - 2.0;
+    let mut rng = StdRng::seed_from_u64(DEFAULT_SEED);
+    vec![
+        shape("disc", "built in: solid disc", |x, y| if x.hypot(y) <= r { 1.0 } else { 0.0 }),
+        shape("blob", "built in: gaussian bump", |x, y| bell(x.hypot(y), 0.0, r / 2.5)),
+        shape("ring", "built in: soft ring", |x, y| bell(x.hypot(y), r * 0.7, r / 6.0)),
+        shape("speckle", "built in: random disc, seed 42", |x, y| if x.hypot(y) <= r { rng.gen() } else { 0.0 }),
+    ]
+}
+
+// The pattern panel: built-in shapes followed by the .pattern files of a
+// directory, and how the selected one is turned before stamping
+pub struct Library {
+    pub open: bool,
+    pub dir: String,
+    pub patterns: Vec<Pattern>,
+    pub selected: usize,
+    pub quarter_turns: u8,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub scale: f64,
+    pub apply_params: bool, // Stamping also sets the pattern's stored parameters
+}
+
+impl Library {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            dir: DEFAULT_PATTERN_DIR.to_string(),
+            patterns: built_in(),
+            selected: 0,
+            quarter_turns: 0,
+            flip_x: false,
+            flip_y: false,
+            scale: 1.0,
+            apply_params: false,
+        }
+    }
+
+    // Built-ins plus every readable pattern in the directory, by file name.
+    // A missing directory just means there are no user patterns
+    pub fn rescan(&mut self) {
+        self.patterns = built_in();
+        let mut paths: Vec<_> = fs::read_dir(&self.dir)
+            .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == PATTERN_EXTENSION)).collect())
+            .unwrap_or_default();
+        paths.sort();
+        for path in paths {
+            match Pattern::load(&path.to_string_lossy()) {
+                Ok(mut pattern) => {
+                    if pattern.name.is_empty() {
+                        pattern.name = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+                    }
+                    self.patterns.push(pattern);
+                }
+                Err(e) => eprintln!("Skipping pattern: {}", e),
+            }
+        }
+        self.selected = self.selected.min(self.patterns.len() - 1);
+    }
+
+    // The selected pattern as it will be stamped
+    pub fn current(&self) -> Option<Pattern> {
+        self.patterns.get(self.selected).map(|p| p.transformed(self.quarter_turns, self.flip_x, self.flip_y, self.scale))
+    }
+
+    // Keys that mean something else while the panel is open. Returns
+    // whether the key was used
+    pub fn handle_key(&mut self, key: Keycode) -> bool {
+        match key {
+            Keycode::Left => self.selected = self.selected.checked_sub(1).unwrap_or(self.patterns.len() - 1),
+            Keycode::Right => self.selected = (self.selected + 1) % self.patterns.len(),
+            Keycode::R => self.quarter_turns = (self.quarter_turns + 1) % 4,
+            Keycode::F => self.flip_x = !self.flip_x,
+            Keycode::V => self.flip_y = !self.flip_y,
+            Keycode::Equals => self.scale = (self.scale * 1.25).min(MAX_SCALE),
+            Keycode::Minus => self.scale = (self.scale / 1.25).max(MIN_SCALE),
+            Keycode::P => self.apply_params = !self.apply_params,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn status(&self) -> String {
+        let name = self.patterns.get(self.selected).map_or("", |p| p.name.as_str());
+        format!(
+            "Library: {} ({}/{})  rot {}°{}{}  x{:.2}  params {}",
+            name,
+            self.selected + 1,
+            self.patterns.len(),
+            self.quarter_turns as u32 * 90,
+            if self.flip_x { " flip-x" } else { "" },
+            if self.flip_y { " flip-y" } else { "" },
+            self.scale,
+            if self.apply_params { "on" } else { "off" }
+        )
+    }
+
+    // First row shown, so the selected pattern stays in view
+    fn first_visible(&self, height: u32) -> usize {
+        let rows = ((height as i32 - PANEL_MARGIN) / ROW_HEIGHT).max(1) as usize;
+        self.selected.saturating_sub(rows - 1)
+    }
+}
+
+impl GameOfLife {
+    // Tab: the library panel, rescanned on every opening so new finds show up
+    pub fn toggle_library(&mut self) {
+        self.library.open = !self.library.open;
+        if self.library.open {
+            self.library.rescan();
+        }
+    }
+
+    // A click picks a thumbnail in the panel or stamps the pattern at the
+    // cell under the cursor
+    pub fn library_click(&mut self, x: i32, y: i32) {
+        if x < PANEL_WIDTH {
+            let row = ((y - PANEL_MARGIN) / ROW_HEIGHT).max(0) as usize;
+            let index = self.library.first_visible(self.height) + row;
+            if index < self.library.patterns.len() {
+                self.library.selected = index;
+            }
+            return;
+        }
+        let Some(pattern) = self.library.current() else { return };
+        let (cx, cy) = self.camera.screen_to_cell(x, y);
+        self.record_history();
+        if self.library.apply_params {
+            self.apply_pattern_params(&pattern);
+        }
+        self.stamp_pattern(&pattern, cx.floor() as i32, cy.floor() as i32);
+        self.apply_mask();
+    }
+
+    // Thumbnails down the left edge and the selected pattern under the cursor
+    pub fn draw_library(&self, canvas: &mut Canvas<Window>) {
+        let color = |v: f64| self.colors[(v * 255.0).clamp(0.0, 255.0) as usize];
+
+        if let Some(pattern) = self.library.current() {
+            let (cx, cy) = self.camera.screen_to_cell(self.mouse_pos.0, self.mouse_pos.1);
+            let (x0, y0) = (cx.floor() as i32 - pattern.width as i32 / 2, cy.floor() as i32 - pattern.height as i32 / 2);
+            for (i, &v) in pattern.cells.iter().enumerate().filter(|(_, &v)| v > 0.01) {
+                let (x, y) = ((x0 + (i % pattern.width) as i32) as f64, (y0 + (i / pattern.width) as i32) as f64);
+                let (sx0, sy0) = self.camera.cell_to_screen(x, y);
+                let (sx1, sy1) = self.camera.cell_to_screen(x + 1.0, y + 1.0);
+                let c = color(v);
+                let _ = canvas.box_(sx0 as i16, sy0 as i16, sx1 as i16 - 1, sy1 as i16 - 1, Color::RGBA(c.r, c.g, c.b, 150));
+            }
+        }
+
+        canvas.set_draw_color(Color::RGBA(15, 15, 20, 230));
+        let _ = canvas.fill_rect(Rect::new(0, 0, PANEL_WIDTH as u32, self.height));
+        let first = self.library.first_visible(self.height);
+        for (row, (index, pattern)) in self.library.patterns.iter().enumerate().skip(first).enumerate() {
+            let top = PANEL_MARGIN + row as i32 * ROW_HEIGHT;
+            if top > self.height as i32 {
+                break;
+            }
+            // Fit the longer side into the thumbnail, centred
+            let cell = THUMB_SIZE as f64 / pattern.width.max(pattern.height).max(1) as f64;
+            let left = PANEL_MARGIN as f64 + (THUMB_SIZE as f64 - cell * pattern.width as f64) / 2.0;
+            let up = top as f64 + (THUMB_SIZE as f64 - cell * pattern.height as f64) / 2.0;
+            for (i, &v) in pattern.cells.iter().enumerate() {
+                let (x, y) = (left + (i % pattern.width) as f64 * cell, up + (i / pattern.width) as f64 * cell);
+                canvas.set_draw_color(color(v));
+                let _ = canvas.fill_rect(Rect::new(x as i32, y as i32, (x + cell) as u32 - x as u32, (y + cell) as u32 - y as u32));
+            }
+            let outline = if index == self.library.selected { Color::RGB(255, 255, 255) } else { Color::RGB(70, 70, 80) };
+            let _ = canvas.rectangle(PANEL_MARGIN as i16 - 1, top as i16 - 1, (PANEL_MARGIN + THUMB_SIZE) as i16, (top + THUMB_SIZE) as i16, outline);
+            let label: String = pattern.name.chars().take(9).collect();
+            let _ = canvas.string(PANEL_MARGIN as i16, (top + THUMB_SIZE + 4) as i16, &label, Color::RGB(220, 220, 220));
+        }
+    }
+}
@@ -1,17 +1,68 @@
+mod analysis;
+mod batch;
+mod brush;
+mod camera;
+mod cli;
+mod clock;
 mod game;
+mod granular;
+mod headless;
+mod history;
+mod image_io;
+mod init;
+mod integrate;
+mod library;
+mod mask;
+mod midi;
+mod modulation;
+mod noise;
+mod npy;
+mod param_map;
+mod params;
+mod pattern;
+mod periodicity;
 mod render;
+mod rewind;
+mod search;
+mod session;
+mod sonify;
+mod spectral;
+mod stats;
+mod sweep;
+mod synth;
+mod tracking;
+mod tuning;
 mod ui;
 mod utils;
+mod wav;
 
 use sdl2::Sdl;
+use sdl2::audio::AudioSpecDesired;
 use rayon::ThreadPoolBuilder;
 
 use game::GameOfLife;
+use synth::{SynthCallback, SAMPLE_RATE};
 use ui::handle_events;
 
 fn main() {
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, cli::usage());
+            std::process::exit(2);
+        }
+    };
+
     ThreadPoolBuilder::new().num_threads(4).build_global().unwrap();
 
+    if options.headless {
+        if let Err(e) = headless::run(&options) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let sdl_context: Sdl = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let ttf_context = sdl2::ttf::init().unwrap();
@@ -26,7 +77,27 @@ fn main() {
     let mut event_pump = sdl_context.event_pump().unwrap();
     let font = ttf_context.load_font("/System/Library/Fonts/SFNS.ttf", 16).unwrap();
 
-    let mut game = GameOfLife::new(750, 750, game::DEFAULT_PIXEL_EDGE_SIZE, &video_subsystem);
+    let mut game = GameOfLife::new(750, 750, game::DEFAULT_PIXEL_EDGE_SIZE);
+    if let Err(e) = game.apply_options(&options) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    // Keep the device alive for the whole run; without audio the simulation still works
+    let desired_spec = AudioSpecDesired { freq: Some(SAMPLE_RATE), channels: Some(2), samples: Some(1024) };
+    let audio_device = sdl_context.audio().and_then(|audio_subsystem| {
+        audio_subsystem.open_playback(None, &desired_spec, |_spec| SynthCallback { synth: game.synth.clone() })
+    });
+    let _audio_device = match audio_device {
+        Ok(device) => {
+            device.resume();
+            Some(device)
+        }
+        Err(e) => {
+            eprintln!("Audio disabled: {}", e);
+            None
+        }
+    };
 
     'running: loop {
         if !handle_events(&mut event_pump, &mut game, &video_subsystem) {
@@ -0,0 +1,96 @@
+use crate::game::GameOfLife;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CellType {
+    Normal,
+    Wall,   // Always 0 and left out of the kernel, like cells past the edge
+    Source, // Always 1
+    Sink,   // Always 0, but neighbours still see it as empty
+}
+
+impl CellType {
+    pub fn cycle(self) -> Self {
+        match self {
+            CellType::Normal => CellType::Wall,
+            CellType::Wall => CellType::Source,
+            CellType::Source => CellType::Sink,
+            CellType::Sink => CellType::Normal,
+        }
+    }
+
+    // Fixed value of the cell, None for normal cells
+    pub fn value(self) -> Option<f64> {
+        match self {
+            CellType::Normal => None,
+            CellType::Wall | CellType::Sink => Some(0.0),
+            CellType::Source => Some(1.0),
+        }
+    }
+
+    // One character per cell in session files
+    pub fn symbol(self) -> char {
+        match self {
+            CellType::Normal => '.',
+            CellType::Wall => '#',
+            CellType::Source => '+',
+            CellType::Sink => '-',
+        }
+    }
+
+    pub fn from_symbol(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(CellType::Normal),
+            '#' => Some(CellType::Wall),
+            '+' => Some(CellType::Source),
+            '-' => Some(CellType::Sink),
+            _ => None,
+        }
+    }
+}
+
+impl GameOfLife {
+    // The mask, if there is one that fits the field
+    pub fn mask_cells(&self) -> Option<&[CellType]> {
+        self.mask.as_deref().filter(|m| m.len() == self.pxl_vec.len())
+    }
+
+    // Pin sources, walls and sinks to their values
+    pub fn apply_mask(&mut self) {
+        if let Some(mask) = self.mask.as_deref().filter(|m| m.len() == self.pxl_vec.len()) {
+            for (cell, kind) in self.pxl_vec.iter_mut().zip(mask) {
+                if let Some(value) = kind.value() {
+                    *cell = value;
+                }
+            }
+        }
+    }
+
+    // Brush dab on the mask layer; erasing makes cells normal again
+    pub fn paint_mask(&mut self, index: usize, erase: bool) {
+        let len = self.pxl_vec.len();
+        let mask = self.mask.get_or_insert_with(Vec::new);
+        if mask.len() != len {
+            *mask = vec![CellType::Normal; len];
+        }
+        mask[index] = if erase { CellType::Normal } else { self.mask_brush };
+        if let Some(value) = mask[index].value() {
+            self.pxl_vec[index] = value;
+        }
+    }
+
+    // Y: the cell type the brush paints on the mask layer
+    pub fn cycle_mask_brush(&mut self) {
+        self.mask_brush = self.mask_brush.cycle();
+        if self.mask_brush == CellType::Normal {
+            self.mask_brush = self.mask_brush.cycle();
+        }
+    }
+
+    pub fn mask_text(&self) -> Option<String> {
+        self.mask_cells().map(|m| m.iter().map(|c| c.symbol()).collect())
+    }
+
+    pub fn parse_mask(text: &str) -> Result<Vec<CellType>, String> {
+        text.chars().map(|c| CellType::from_symbol(c).ok_or(format!("bad mask cell '{}'", c))).collect()
+    }
+}
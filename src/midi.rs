@@ -0,0 +1,453 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use crate::game::GameOfLife;
+use crate::params::{param_spec, PARAMETERS};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MidiMessage {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+}
+
+// Number of data bytes after a channel status byte
+fn data_len(status: u8) -> usize {
+    match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    }
+}
+
+// Everything but notes and controllers is skipped
+fn channel_message(status: u8, data: &[u8]) -> Option<MidiMessage> {
+    let channel = status & 0x0F;
+    match (status & 0xF0, data) {
+        (0x90, &[note, velocity]) if velocity > 0 => Some(MidiMessage::NoteOn { channel, note, velocity }),
+        (0x80 | 0x90, &[note, _]) => Some(MidiMessage::NoteOff { channel, note }),
+        (0xB0, &[controller, value]) => Some(MidiMessage::ControlChange { channel, controller, value }),
+        _ => None,
+    }
+}
+
+// Anything that produces MIDI messages. Sources are polled with the tempo
+// clock's position in beats; live sources ignore it and return whatever
+// arrived since the last poll
+pub trait MidiSource {
+    fn name(&self) -> String;
+    fn poll(&mut self, beats: f64) -> Vec<MidiMessage>;
+}
+
+// Replays a Standard MIDI File with its quarter notes on the beats of the
+// simulation clock, so a headless run gets the same input every time
+#[derive(Clone)]
+pub struct MidiFileSource {
+    path: String,
+    events: Vec<(f64, MidiMessage)>, // (beat, message), sorted
+    next: usize,
+    last_beats: f64,
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut value = 0u32;
+    for _ in 0..4 {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value = (value << 7) | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn parse_track(data: &[u8], division: f64, events: &mut Vec<(f64, MidiMessage)>) -> Option<()> {
+    let mut pos = 0;
+    let mut tick = 0u64;
+    let mut running = None;
+    while pos < data.len() {
+        tick += read_varint(data, &mut pos)? as u64;
+        let mut status = *data.get(pos)?;
+        match status {
+            0xFF => {
+                // Meta event: type, length, data
+                pos += 2;
+                let len = read_varint(data, &mut pos)?;
+                pos += len as usize;
+            }
+            0xF0 | 0xF7 => {
+                pos += 1;
+                let len = read_varint(data, &mut pos)?;
+                pos += len as usize;
+            }
+            _ => {
+                if status & 0x80 != 0 {
+                    running = Some(status);
+                    pos += 1;
+                } else {
+                    status = running?;
+                }
+                let len = data_len(status);
+                let bytes = data.get(pos..pos + len)?;
+                pos += len;
+                if let Some(message) = channel_message(status, bytes) {
+                    events.push((tick as f64 / division, message));
+                }
+            }
+        }
+    }
+    Some(())
+}
+
+impl MidiFileSource {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        if data.len() < 14 || &data[0..4] != b"MThd" {
+            return Err(format!("{}: not a MIDI file", path));
+        }
+        let division = u16::from_be_bytes([data[12], data[13]]);
+        if division & 0x8000 != 0 || division == 0 {
+            return Err(format!("{}: SMPTE time division is not supported", path));
+        }
+
+        let mut events = Vec::new();
+        let mut pos = 8 + u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        while pos + 8 <= data.len() {
+            let size = u32::from_be_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+            let body = pos + 8;
+            let end = (body + size).min(data.len());
+            if &data[pos..pos + 4] == b"MTrk" {
+                parse_track(&data[body..end], division as f64, &mut events).ok_or(format!("{}: corrupt track", path))?;
+            }
+            pos = body + size;
+        }
+        // Stable, so simultaneous events keep their order within a track
+        events.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(Self { path: path.to_string(), events, next: 0, last_beats: 0.0 })
+    }
+}
+
+impl MidiSource for MidiFileSource {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn poll(&mut self, beats: f64) -> Vec<MidiMessage> {
+        // Rewinding the simulation rewinds the file too
+        if beats < self.last_beats {
+            self.next = self.events.partition_point(|e| e.0 < beats);
+        }
+        self.last_beats = beats;
+        let start = self.next;
+        while self.next < self.events.len() && self.events[self.next].0 <= beats {
+            self.next += 1;
+        }
+        self.events[start..self.next].iter().map(|e| e.1).collect()
+    }
+}
+
+// Turns a raw byte stream into messages, with running status
+#[derive(Default)]
+struct StreamParser {
+    status: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl StreamParser {
+    fn feed(&mut self, byte: u8) -> Option<MidiMessage> {
+        if byte >= 0xF8 {
+            return None; // Realtime bytes may appear anywhere
+        }
+        if byte & 0x80 != 0 {
+            // System common messages cancel running status
+            self.status = if byte < 0xF0 { Some(byte) } else { None };
+            self.data.clear();
+            return None;
+        }
+        let status = self.status?;
+        self.data.push(byte);
+        if self.data.len() < data_len(status) {
+            return None;
+        }
+        let message = channel_message(status, &self.data);
+        self.data.clear();
+        message
+    }
+}
+
+// A raw MIDI device such as /dev/snd/midiC1D0 or /dev/midi1. A reader thread
+// blocks on the device and hands messages over a channel
+pub struct RawPortSource {
+    path: String,
+    receiver: Receiver<MidiMessage>,
+}
+
+impl RawPortSource {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let mut device = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut parser = StreamParser::default();
+            let mut buffer = [0u8; 256];
+            while let Ok(n) = device.read(&mut buffer) {
+                if n == 0 {
+                    break;
+                }
+                for &byte in &buffer[..n] {
+                    if let Some(message) = parser.feed(byte) {
+                        if sender.send(message).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Self { path: path.to_string(), receiver })
+    }
+}
+
+impl MidiSource for RawPortSource {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn poll(&mut self, _beats: f64) -> Vec<MidiMessage> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MidiControl {
+    Cc(u8),
+    Note(u8),
+    AnyNote,
+}
+
+impl MidiControl {
+    fn matches(self, message: &MidiMessage) -> bool {
+        match (self, *message) {
+            (MidiControl::Cc(c), MidiMessage::ControlChange { controller, .. }) => c == controller,
+            (MidiControl::Note(n), MidiMessage::NoteOn { note, .. }) => n == note,
+            (MidiControl::AnyNote, MidiMessage::NoteOn { .. }) => true,
+            _ => false,
+        }
+    }
+
+    fn of(message: &MidiMessage) -> Option<Self> {
+        match *message {
+            MidiMessage::ControlChange { controller, .. } => Some(MidiControl::Cc(controller)),
+            MidiMessage::NoteOn { note, .. } => Some(MidiControl::Note(note)),
+            MidiMessage::NoteOff { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum MidiAction {
+    Param(String),
+    Stamp, // Dab of matter: note picks the column, velocity the row
+}
+
+// "cc74:bell_m", "note60:kernel_rad", "notes:stamp"
+#[derive(Clone, Debug)]
+pub struct MidiBinding {
+    pub control: MidiControl,
+    pub action: MidiAction,
+}
+
+impl MidiBinding {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (control, action) = spec.split_once(':').ok_or(format!("MIDI binding '{}' is not control:target", spec))?;
+        let number = |t: &str| t.parse::<u8>().ok().filter(|&n| n < 128).ok_or(format!("bad MIDI number '{}'", t));
+        let control = if control == "notes" {
+            MidiControl::AnyNote
+        } else if let Some(n) = control.strip_prefix("cc") {
+            MidiControl::Cc(number(n)?)
+        } else if let Some(n) = control.strip_prefix("note") {
+            MidiControl::Note(number(n)?)
+        } else {
+            return Err(format!("unknown MIDI control '{}'", control));
+        };
+        let action = match action {
+            "stamp" => MidiAction::Stamp,
+            name if param_spec(name).is_some() => MidiAction::Param(name.to_string()),
+            name => return Err(format!("unknown parameter '{}'", name)),
+        };
+        Ok(Self { control, action })
+    }
+
+    pub fn spec(&self) -> String {
+        let control = match self.control {
+            MidiControl::Cc(n) => format!("cc{}", n),
+            MidiControl::Note(n) => format!("note{}", n),
+            MidiControl::AnyNote => "notes".to_string(),
+        };
+        let action = match &self.action {
+            MidiAction::Param(name) => name.as_str(),
+            MidiAction::Stamp => "stamp",
+        };
+        format!("{}:{}", control, action)
+    }
+}
+
+// Targets offered by MIDI learn, in the order U cycles through them
+pub fn learn_targets() -> impl Iterator<Item = MidiAction> {
+    PARAMETERS.iter().map(|p| MidiAction::Param(p.name.to_string())).chain(std::iter::once(MidiAction::Stamp))
+}
+
+impl GameOfLife {
+    // Poll every source and act on the messages. Called once per frame and
+    // before every step, so file replay stays on the generation clock
+    pub fn process_midi(&mut self) {
+        let beats = self.generation as f64 / self.clock.steps_per_beat();
+        let mut messages = Vec::new();
+        for source in &mut self.midi_sources {
+            messages.extend(source.poll(beats));
+        }
+        for message in messages {
+            self.handle_midi(message);
+        }
+    }
+
+    pub fn handle_midi(&mut self, message: MidiMessage) {
+        self.last_midi = Some(message);
+
+        // MIDI learn binds the first control that moves to the chosen target
+        if let Some(action) = self.midi_learn.clone() {
+            if let Some(control) = MidiControl::of(&message) {
+                self.midi_bindings.retain(|b| b.control != control);
+                let binding = MidiBinding { control, action };
+                println!("MIDI learn: {}", binding.spec());
+                self.midi_bindings.push(binding);
+                self.midi_learn = None;
+            }
+            return;
+        }
+
+        let actions: Vec<MidiAction> =
+            self.midi_bindings.iter().filter(|b| b.control.matches(&message)).map(|b| b.action.clone()).collect();
+        for action in actions {
+            match (action, message) {
+                (MidiAction::Param(name), MidiMessage::ControlChange { value, .. })
+                | (MidiAction::Param(name), MidiMessage::NoteOn { velocity: value, .. }) => {
+                    if let Some(spec) = param_spec(&name) {
+                        self.set_parameter(&name, spec.min + value as f64 / 127.0 * (spec.max - spec.min));
+                    }
+                }
+                (MidiAction::Stamp, MidiMessage::NoteOn { note, velocity, .. }) => {
+                    let cx = (note as f64 / 127.0 * (self.a_width - 1) as f64) as i32;
+                    let cy = ((1.0 - velocity as f64 / 127.0) * (self.a_height - 1) as f64) as i32;
+                    self.inject(cx, cy, 1.0);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn midi_status(&self) -> String {
+        let learn = match &self.midi_learn {
+            Some(MidiAction::Param(name)) => format!("  LEARN {} (move a control)", name),
+            Some(MidiAction::Stamp) => "  LEARN stamp (play a note)".to_string(),
+            None => String::new(),
+        };
+        let last = self.last_midi.map(|m| format!("  Last: {:?}", m)).unwrap_or_default();
+        let sources: Vec<String> = self.midi_sources.iter().map(|s| s.name()).collect();
+        format!("MIDI: [{}]  {} bindings{}{}", sources.join(", "), self.midi_bindings.len(), learn, last)
+    }
+
+    // U: off -> first target -> ... -> last target -> off
+    pub fn cycle_midi_learn(&mut self) {
+        let targets: Vec<MidiAction> = learn_targets().collect();
+        self.midi_learn = match &self.midi_learn {
+            None => targets.first().cloned(),
+            Some(current) => targets.iter().skip_while(|t| *t != current).nth(1).cloned(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(note: u8, velocity: u8) -> MidiMessage {
+        MidiMessage::NoteOn { channel: 0, note, velocity }
+    }
+
+    #[test]
+    fn track_running_status_and_skipped_events() {
+        let track = [
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // Tempo meta event
+            0x00, 0x90, 60, 100, // Note on
+            0x60, 62, 90,        // Running status, one beat later at 96 ticks per beat
+            0x00, 0xF0, 0x02, 0x7E, 0xF7, // Sysex
+            0x30, 60, 0,         // Running status survives the skipped events; velocity 0 is a note off
+            0x00, 0xB1, 74, 64,  // Controller on channel 2
+            0x00, 0xC0, 5,       // Program change has one data byte and is dropped
+            0x00, 0xFF, 0x2F, 0x00, // End of track
+        ];
+        let mut events = Vec::new();
+        assert!(parse_track(&track, 96.0, &mut events).is_some());
+        assert_eq!(events, vec![
+            (0.0, note_on(60, 100)),
+            (1.0, note_on(62, 90)),
+            (1.5, MidiMessage::NoteOff { channel: 0, note: 60 }),
+            (1.5, MidiMessage::ControlChange { channel: 1, controller: 74, value: 64 }),
+        ]);
+    }
+
+    #[test]
+    fn track_without_status_is_corrupt() {
+        assert!(parse_track(&[0x00, 60, 100], 96.0, &mut Vec::new()).is_none());
+    }
+
+    #[test]
+    fn stream_split_messages_and_realtime() {
+        let mut parser = StreamParser::default();
+        let bytes = [0x90, 0xF8, 60, 0xFE, 100, 62, 0xF8, 80, 0xB0, 1, 0xFA, 7];
+        let messages: Vec<MidiMessage> = bytes.iter().filter_map(|&b| parser.feed(b)).collect();
+        assert_eq!(messages, vec![
+            note_on(60, 100),
+            note_on(62, 80),
+            MidiMessage::ControlChange { channel: 0, controller: 1, value: 7 },
+        ]);
+    }
+
+    #[test]
+    fn stream_system_common_cancels_running_status() {
+        let mut parser = StreamParser::default();
+        let messages: Vec<MidiMessage> = [0x90, 60, 100, 0xF3, 1, 62, 90].iter().filter_map(|&b| parser.feed(b)).collect();
+        assert_eq!(messages, vec![note_on(60, 100)]);
+    }
+
+    #[test]
+    fn binding_round_trip() {
+        for spec in ["cc74:bell_m", "note60:kernel_rad", "notes:stamp"] {
+            assert_eq!(MidiBinding::parse(spec).unwrap().spec(), spec);
+        }
+        assert!(MidiBinding::parse("cc128:bell_m").is_err());
+        assert!(MidiBinding::parse("cc1:nonsense").is_err());
+        assert!(MidiBinding::parse("pitch:bell_m").is_err());
+    }
+
+    #[test]
+    fn file_poll_timing_and_rewind() {
+        let mut source = MidiFileSource {
+            path: String::new(),
+            events: vec![(0.0, note_on(60, 1)), (1.0, note_on(61, 1)), (1.0, note_on(62, 1)), (2.5, note_on(63, 1))],
+            next: 0,
+            last_beats: 0.0,
+        };
+        assert_eq!(source.poll(0.0), vec![note_on(60, 1)]);
+        assert_eq!(source.poll(0.5), vec![]);
+        assert_eq!(source.poll(1.0), vec![note_on(61, 1), note_on(62, 1)]);
+        assert_eq!(source.poll(3.0), vec![note_on(63, 1)]);
+        assert_eq!(source.poll(4.0), vec![]);
+        // Looping back replays from the new position
+        assert_eq!(source.poll(0.75), vec![]);
+        assert_eq!(source.poll(2.5), vec![note_on(61, 1), note_on(62, 1), note_on(63, 1)]);
+        assert_eq!(source.poll(0.0), vec![note_on(60, 1)]);
+    }
+}
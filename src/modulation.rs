@@ -0,0 +1,471 @@
+use rand::{Rng, rngs::StdRng, SeedableRng};
+use crate::analysis::AudioAnalysis;
+use crate::clock::Clock;
+use crate::game::GameOfLife;
+use crate::params::param_spec;
+
+pub const INJECT_THRESHOLD: f64 = 0.5;
+const INJECT_RADIUS: i32 = 5; // Cells, independent of the brush
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AudioBand {
+    Envelope,
+    Low,
+    Mid,
+    High,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ModSource {
+    Audio(AudioBand),
+    Lfo(usize),
+    Envelope(usize),
+}
+
+impl ModSource {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "audio_env" => Some(ModSource::Audio(AudioBand::Envelope)),
+            "audio_low" => Some(ModSource::Audio(AudioBand::Low)),
+            "audio_mid" => Some(ModSource::Audio(AudioBand::Mid)),
+            "audio_high" => Some(ModSource::Audio(AudioBand::High)),
+            _ => {
+                if let Some(i) = name.strip_prefix("lfo") {
+                    i.parse().ok().map(ModSource::Lfo)
+                } else {
+                    name.strip_prefix("env").and_then(|i| i.parse().ok()).map(ModSource::Envelope)
+                }
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            ModSource::Audio(AudioBand::Envelope) => "audio_env".to_string(),
+            ModSource::Audio(AudioBand::Low) => "audio_low".to_string(),
+            ModSource::Audio(AudioBand::Mid) => "audio_mid".to_string(),
+            ModSource::Audio(AudioBand::High) => "audio_high".to_string(),
+            ModSource::Lfo(i) => format!("lfo{}", i),
+            ModSource::Envelope(i) => format!("env{}", i),
+        }
+    }
+}
+
+// Times of LFOs and envelopes count generations, seconds of simulation time
+// (generations * seconds_per_step) or beats of the tempo clock
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimeUnit {
+    Generations,
+    Seconds,
+    Beats,
+}
+
+impl TimeUnit {
+    fn steps(self, amount: f64, seconds_per_step: f64, steps_per_beat: f64) -> f64 {
+        match self {
+            TimeUnit::Generations => amount,
+            TimeUnit::Seconds => amount / seconds_per_step,
+            TimeUnit::Beats => amount * steps_per_beat,
+        }
+    }
+}
+
+// "4s" is four seconds, "2b" two beats, "200g" or "200" two hundred generations
+fn parse_time(text: &str) -> Result<(f64, TimeUnit), String> {
+    let (number, unit) = if let Some(n) = text.strip_suffix('s') {
+        (n, TimeUnit::Seconds)
+    } else if let Some(n) = text.strip_suffix('b') {
+        (n, TimeUnit::Beats)
+    } else {
+        (text.strip_suffix('g').unwrap_or(text), TimeUnit::Generations)
+    };
+    let value: f64 = number.parse().map_err(|_| format!("bad time '{}'", text))?;
+    Ok((value, unit))
+}
+
+fn format_time(value: f64, unit: TimeUnit) -> String {
+    match unit {
+        TimeUnit::Generations => format!("{}g", value),
+        TimeUnit::Seconds => format!("{}s", value),
+        TimeUnit::Beats => format!("{}b", value),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    SampleHold,
+}
+
+// Bipolar low-frequency oscillator, -1..1
+#[derive(Clone, Debug)]
+pub struct Lfo {
+    pub shape: LfoShape,
+    pub period: f64,
+    pub unit: TimeUnit,
+    pub phase: f64, // 0..1
+    pub held: f64,  // Current sample-and-hold value
+}
+
+impl Lfo {
+    // "sine:4s", "triangle:300g", "sh:2s"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (shape, period) = spec.split_once(':').ok_or(format!("LFO '{}' is not shape:period", spec))?;
+        let shape = match shape {
+            "sine" => LfoShape::Sine,
+            "triangle" => LfoShape::Triangle,
+            "sh" => LfoShape::SampleHold,
+            _ => return Err(format!("unknown LFO shape '{}'", shape)),
+        };
+        let (period, unit) = parse_time(period)?;
+        if period <= 0.0 {
+            return Err("LFO period must be positive".to_string());
+        }
+        Ok(Self { shape, period, unit, phase: 0.0, held: 0.0 })
+    }
+
+    pub fn spec(&self) -> String {
+        let shape = match self.shape {
+            LfoShape::Sine => "sine",
+            LfoShape::Triangle => "triangle",
+            LfoShape::SampleHold => "sh",
+        };
+        format!("{}:{}", shape, format_time(self.period, self.unit))
+    }
+
+    pub fn value(&self) -> f64 {
+        match self.shape {
+            LfoShape::Sine => (self.phase * std::f64::consts::TAU).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            LfoShape::SampleHold => self.held,
+        }
+    }
+
+    fn advance(&mut self, seconds_per_step: f64, steps_per_beat: f64, rng: &mut StdRng) {
+        self.phase += 1.0 / self.unit.steps(self.period, seconds_per_step, steps_per_beat).max(1e-9);
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+            self.held = rng.gen_range(-1.0..1.0);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EnvelopeTrigger {
+    Key,   // Return key in the viewer
+    Loop,  // Retriggers as soon as it has finished
+    Birth, // A creature was born
+    Beat,  // Every beat of the tempo clock
+    Bar,   // Every downbeat
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum EnvelopeStage {
+    Idle,
+    Attack,
+    Decay,
+    Hold,
+    Release,
+}
+
+// One-shot attack / decay / hold at sustain / release envelope, 0..1
+#[derive(Clone, Debug)]
+pub struct Envelope {
+    pub attack: f64,
+    pub decay: f64,
+    pub sustain: f64, // Level, 0..1
+    pub hold: f64,
+    pub release: f64,
+    pub unit: TimeUnit,
+    pub trigger: EnvelopeTrigger,
+    stage: EnvelopeStage,
+    elapsed: f64, // Steps spent in the current stage
+    pub level: f64,
+}
+
+impl Envelope {
+    // "attack:decay:sustain:hold:release<unit>:trigger", e.g. "1:2:0.5:4:8s:birth"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let [attack, decay, sustain, hold, release, trigger] = parts[..] else {
+            return Err(format!("envelope '{}' is not attack:decay:sustain:hold:release:trigger", spec));
+        };
+        let (release, unit) = parse_time(release)?;
+        let number = |t: &str| t.parse::<f64>().map_err(|_| format!("bad envelope value '{}'", t));
+        let trigger = match trigger {
+            "key" => EnvelopeTrigger::Key,
+            "loop" => EnvelopeTrigger::Loop,
+            "birth" => EnvelopeTrigger::Birth,
+            "beat" => EnvelopeTrigger::Beat,
+            "bar" => EnvelopeTrigger::Bar,
+            _ => return Err(format!("unknown envelope trigger '{}'", trigger)),
+        };
+        Ok(Self {
+            attack: number(attack)?,
+            decay: number(decay)?,
+            sustain: number(sustain)?.clamp(0.0, 1.0),
+            hold: number(hold)?,
+            release,
+            unit,
+            trigger,
+            stage: if trigger == EnvelopeTrigger::Loop { EnvelopeStage::Attack } else { EnvelopeStage::Idle },
+            elapsed: 0.0,
+            level: 0.0,
+        })
+    }
+
+    pub fn spec(&self) -> String {
+        let trigger = match self.trigger {
+            EnvelopeTrigger::Key => "key",
+            EnvelopeTrigger::Loop => "loop",
+            EnvelopeTrigger::Birth => "birth",
+            EnvelopeTrigger::Beat => "beat",
+            EnvelopeTrigger::Bar => "bar",
+        };
+        format!("{}:{}:{}:{}:{}:{}", self.attack, self.decay, self.sustain, self.hold, format_time(self.release, self.unit), trigger)
+    }
+
+    // Running state for session files: "stage elapsed level"
+    pub fn state(&self) -> String {
+        format!("{:?} {} {}", self.stage, self.elapsed, self.level)
+    }
+
+    pub fn restore_state(&mut self, state: &str) -> Result<(), String> {
+        let parts: Vec<&str> = state.split_whitespace().collect();
+        let [stage, elapsed, level] = parts[..] else {
+            return Err(format!("bad envelope state '{}'", state));
+        };
+        self.stage = match stage {
+            "Idle" => EnvelopeStage::Idle,
+            "Attack" => EnvelopeStage::Attack,
+            "Decay" => EnvelopeStage::Decay,
+            "Hold" => EnvelopeStage::Hold,
+            "Release" => EnvelopeStage::Release,
+            _ => return Err(format!("unknown envelope stage '{}'", stage)),
+        };
+        self.elapsed = elapsed.parse().map_err(|_| format!("bad envelope state '{}'", state))?;
+        self.level = level.parse().map_err(|_| format!("bad envelope state '{}'", state))?;
+        Ok(())
+    }
+
+    pub fn trigger(&mut self) {
+        self.stage = EnvelopeStage::Attack;
+        self.elapsed = 0.0;
+    }
+
+    fn advance(&mut self, seconds_per_step: f64, steps_per_beat: f64) {
+        let steps = |t: f64| self.unit.steps(t, seconds_per_step, steps_per_beat).max(1e-9);
+        self.elapsed += 1.0;
+        let t = self.elapsed;
+        let (level, done) = match self.stage {
+            EnvelopeStage::Idle => (0.0, false),
+            EnvelopeStage::Attack => ((t / steps(self.attack)).min(1.0), t >= steps(self.attack)),
+            EnvelopeStage::Decay => {
+                let x = (t / steps(self.decay)).min(1.0);
+                (1.0 + (self.sustain - 1.0) * x, t >= steps(self.decay))
+            }
+            EnvelopeStage::Hold => (self.sustain, t >= steps(self.hold)),
+            EnvelopeStage::Release => {
+                let x = (t / steps(self.release)).min(1.0);
+                (self.sustain * (1.0 - x), t >= steps(self.release))
+            }
+        };
+        self.level = level;
+        if done {
+            self.elapsed = 0.0;
+            self.stage = match self.stage {
+                EnvelopeStage::Attack => EnvelopeStage::Decay,
+                EnvelopeStage::Decay => EnvelopeStage::Hold,
+                EnvelopeStage::Hold => EnvelopeStage::Release,
+                EnvelopeStage::Release if self.trigger == EnvelopeTrigger::Loop => EnvelopeStage::Attack,
+                _ => EnvelopeStage::Idle,
+            };
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ModTarget {
+    Param(String),
+    Inject, // Stamp the brush at a random cell on each onset of the source
+}
+
+#[derive(Clone, Debug)]
+pub struct ModRoute {
+    pub source: ModSource,
+    pub target: ModTarget,
+    pub depth: f64, // Fraction of the parameter's range at full source value
+}
+
+impl ModRoute {
+    // "source:target:depth", e.g. "audio_low:bell_m:0.05" or "audio_env:inject:1"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let [source, target, depth] = parts[..] else {
+            return Err(format!("route '{}' is not source:target:depth", spec));
+        };
+        let source = ModSource::from_name(source).ok_or(format!("unknown modulation source '{}'", source))?;
+        let target = match target {
+            "inject" => ModTarget::Inject,
+            name if param_spec(name).is_some() => ModTarget::Param(name.to_string()),
+            name => return Err(format!("unknown parameter '{}'", name)),
+        };
+        let depth = depth.parse().map_err(|_| format!("bad depth '{}'", depth))?;
+        Ok(Self { source, target, depth })
+    }
+
+    pub fn spec(&self) -> String {
+        let target = match &self.target {
+            ModTarget::Param(name) => name.as_str(),
+            ModTarget::Inject => "inject",
+        };
+        format!("{}:{}:{}", self.source.name(), target, self.depth)
+    }
+}
+
+#[derive(Clone)]
+pub struct ModMatrix {
+    pub routes: Vec<ModRoute>,
+    pub lfos: Vec<Lfo>,
+    pub envelopes: Vec<Envelope>,
+    pub audio: Option<AudioAnalysis>,
+    pub seconds_per_step: f64, // Both follow the tempo clock, see GameOfLife::sync_clock
+    pub steps_per_beat: f64,
+    pub time: f64, // Position in the input track, advanced once per generation
+    inject_rng: StdRng,
+    inject_above: Vec<bool>,
+}
+
+impl ModMatrix {
+    pub fn new(seed: u64) -> Self {
+        let clock = Clock::new();
+        Self {
+            routes: Vec::new(),
+            lfos: Vec::new(),
+            envelopes: Vec::new(),
+            audio: None,
+            seconds_per_step: clock.seconds_per_step(),
+            steps_per_beat: clock.steps_per_beat(),
+            time: 0.0,
+            inject_rng: StdRng::seed_from_u64(seed),
+            inject_above: Vec::new(),
+        }
+    }
+
+    // New random stream for injections and sample-and-hold LFOs, keeping the routes
+    pub fn reseed(&mut self, seed: u64) {
+        self.inject_rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn source_value(&self, source: ModSource) -> f64 {
+        match source {
+            ModSource::Audio(band) => {
+                let features = self.audio.as_ref().map(|a| a.features_at(self.time)).unwrap_or_default();
+                match band {
+                    AudioBand::Envelope => features.envelope,
+                    AudioBand::Low => features.low,
+                    AudioBand::Mid => features.mid,
+                    AudioBand::High => features.high,
+                }
+            }
+            ModSource::Lfo(i) => self.lfos.get(i).map_or(0.0, Lfo::value),
+            ModSource::Envelope(i) => self.envelopes.get(i).map_or(0.0, |e| e.level),
+        }
+    }
+
+    pub fn trigger_envelopes(&mut self, trigger: EnvelopeTrigger) {
+        for envelope in self.envelopes.iter_mut().filter(|e| e.trigger == trigger) {
+            envelope.trigger();
+        }
+    }
+
+    // Move every source on by one generation
+    pub fn advance(&mut self) {
+        for lfo in &mut self.lfos {
+            lfo.advance(self.seconds_per_step, self.steps_per_beat, &mut self.inject_rng);
+        }
+        for envelope in &mut self.envelopes {
+            envelope.advance(self.seconds_per_step, self.steps_per_beat);
+        }
+        self.time += self.seconds_per_step;
+    }
+}
+
+impl GameOfLife {
+    // Offset the routed parameters for the coming step and fire brush injections.
+    // Returns the unmodulated values, which restore_modulation puts back so
+    // keys and sliders keep editing the base value
+    pub fn apply_modulation(&mut self) -> Vec<(String, f64)> {
+        let mut offsets: Vec<(String, f64)> = Vec::new();
+        let mut injections = Vec::new();
+        let route_count = self.modulation.routes.len();
+        self.modulation.inject_above.resize(route_count, false);
+
+        for (i, route) in self.modulation.routes.iter().enumerate() {
+            let value = self.modulation.source_value(route.source);
+            match &route.target {
+                ModTarget::Param(name) => {
+                    if let Some(spec) = param_spec(name) {
+                        offsets.push((name.clone(), value * route.depth * (spec.max - spec.min)));
+                    }
+                }
+                ModTarget::Inject => {
+                    let above = value * route.depth.abs() > INJECT_THRESHOLD;
+                    if above && !self.modulation.inject_above[i] {
+                        injections.push(value);
+                    }
+                    self.modulation.inject_above[i] = above;
+                }
+            }
+        }
+
+        for strength in injections {
+            let cx = self.modulation.inject_rng.gen_range(0..self.a_width) as i32;
+            let cy = self.modulation.inject_rng.gen_range(0..self.a_height) as i32;
+            self.inject(cx, cy, strength.clamp(0.0, 1.0));
+        }
+
+        let mut base = Vec::new();
+        for (name, offset) in offsets {
+            let Some(value) = self.get_parameter(&name) else { continue };
+            if !base.iter().any(|(n, _)| *n == name) {
+                base.push((name.clone(), value));
+            }
+            self.set_parameter(&name, value + offset);
+        }
+        base
+    }
+
+    // Adds a soft round dab of matter to the field, whatever layer or brush
+    // mode the user has selected. Used by inject routes and MIDI stamps
+    pub fn inject(&mut self, cx: i32, cy: i32, strength: f64) {
+        for dy in -INJECT_RADIUS..=INJECT_RADIUS {
+            for dx in -INJECT_RADIUS..=INJECT_RADIUS {
+                let (nx, ny) = (cx + dx, cy + dy);
+                let falloff = 1.0 - ((dx * dx + dy * dy) as f64).sqrt() / INJECT_RADIUS as f64;
+                if falloff > 0.0 && nx >= 0 && ny >= 0 && (nx as u32) < self.a_width && (ny as u32) < self.a_height {
+                    let index = ny as usize * self.a_width as usize + nx as usize;
+                    self.pxl_vec[index] = (self.pxl_vec[index] + strength * falloff).min(1.0);
+                }
+            }
+        }
+    }
+
+    // "lfo0 0.53 env0 0.20" for the info window
+    pub fn modulation_sources(&self) -> String {
+        let lfos = (0..self.modulation.lfos.len()).map(ModSource::Lfo);
+        let envelopes = (0..self.modulation.envelopes.len()).map(ModSource::Envelope);
+        lfos.chain(envelopes)
+            .map(|source| format!("{} {:.2}", source.name(), self.modulation.source_value(source)))
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+
+    pub fn restore_modulation(&mut self, base: Vec<(String, f64)>) {
+        for (name, value) in base {
+            self.set_parameter(&name, value);
+        }
+        self.modulation.advance();
+    }
+}
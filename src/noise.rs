@@ -0,0 +1,246 @@
+use std::f64::consts::{SQRT_2, TAU};
+use rand::{Rng, rngs::StdRng, SeedableRng};
+use crate::game::GameOfLife;
+use crate::pattern::{stamp, Pattern};
+use crate::utils::bell;
+
+const DEFAULT_SCALE: f64 = 16.0;
+const DEFAULT_TAU: f64 = 20.0;
+const DEFAULT_RATE: f64 = 0.05;
+const DEFAULT_RADIUS: f64 = 5.0;
+const INITIAL_DROP_GENERATIONS: f64 = 100.0; // A fresh field gets this many generations of drops
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NoiseKind {
+    Uniform,  // White noise in ±1
+    Gaussian, // White noise, unit variance
+    Perlin,   // Smooth in space, white in time
+    Ou,       // Ornstein-Uhlenbeck: white in space, correlated in time
+    Drops,    // Sparse blobs or pattern stamps at random places
+}
+
+impl NoiseKind {
+    pub const ALL: [NoiseKind; 5] = [NoiseKind::Uniform, NoiseKind::Gaussian, NoiseKind::Perlin, NoiseKind::Ou, NoiseKind::Drops];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NoiseKind::Uniform => "uniform",
+            NoiseKind::Gaussian => "gaussian",
+            NoiseKind::Perlin => "perlin",
+            NoiseKind::Ou => "ou",
+            NoiseKind::Drops => "drops",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.name() == name)
+    }
+}
+
+// How the field is disturbed every generation. Written "kind:key=value:...",
+// e.g. "perlin:scale=24:seed=7" or "drops:rate=0.1:pattern=orbium.pattern".
+// A model without a seed draws from the global noise seed
+#[derive(Clone)]
+pub struct NoiseModel {
+    pub kind: NoiseKind,
+    pub seed: Option<u64>,
+    pub scale: f64,  // Perlin: cells per lattice square
+    pub tau: f64,    // OU: correlation time in generations
+    pub rate: f64,   // Drops: expected drops per generation
+    pub radius: f64, // Drops: blob radius in cells
+    pub pattern: Option<(String, Pattern)>, // Drops: stamped instead of a blob
+    ou: Vec<f64>,    // OU state of every cell, unit variance
+}
+
+impl NoiseModel {
+    pub fn new(kind: NoiseKind) -> Self {
+        Self {
+            kind,
+            seed: None,
+            scale: DEFAULT_SCALE,
+            tau: DEFAULT_TAU,
+            rate: DEFAULT_RATE,
+            radius: DEFAULT_RADIUS,
+            pattern: None,
+            ou: Vec::new(),
+        }
+    }
+
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.split(':');
+        let name = parts.next().unwrap_or("");
+        let mut model = Self::new(NoiseKind::from_name(name).ok_or(format!("unknown noise model '{}'", name))?);
+        for part in parts {
+            let (key, value) = part.split_once('=').ok_or(format!("noise setting '{}' is not key=value", part))?;
+            let positive = || match value.parse::<f64>() {
+                Ok(v) if v > 0.0 => Ok(v),
+                _ => Err(format!("{} needs a positive number", key)),
+            };
+            match key {
+                "seed" => model.seed = Some(value.parse().map_err(|_| format!("bad seed '{}'", value))?),
+                "scale" => model.scale = positive()?,
+                "tau" => model.tau = positive()?,
+                "rate" => model.rate = positive()?,
+                "radius" => model.radius = positive()?,
+                "pattern" => model.pattern = Some((value.to_string(), Pattern::load(value)?)),
+                _ => return Err(format!("unknown noise setting '{}'", key)),
+            }
+        }
+        Ok(model)
+    }
+
+    pub fn spec(&self) -> String {
+        let mut spec = self.kind.name().to_string();
+        match self.kind {
+            NoiseKind::Uniform | NoiseKind::Gaussian => {}
+            NoiseKind::Perlin => spec += &format!(":scale={}", self.scale),
+            NoiseKind::Ou => spec += &format!(":tau={}", self.tau),
+            NoiseKind::Drops => {
+                spec += &format!(":rate={}:radius={}", self.rate, self.radius);
+                if let Some((path, _)) = &self.pattern {
+                    spec += &format!(":pattern={}", path);
+                }
+            }
+        }
+        if let Some(seed) = self.seed {
+            spec += &format!(":seed={}", seed);
+        }
+        spec
+    }
+
+    // Same settings, next kind
+    pub fn cycle(&mut self) {
+        let idx = NoiseKind::ALL.iter().position(|&k| k == self.kind).unwrap_or(0);
+        self.kind = NoiseKind::ALL[(idx + 1) % NoiseKind::ALL.len()];
+        self.ou.clear();
+    }
+
+    // One generation of noise, scaled by `amplitude`. Drops are events rather
+    // than a diffusion, so they keep their full height whatever the amplitude
+    pub fn apply(&mut self, field: &mut [f64], width: u32, height: u32, generation: u64, amplitude: f64, global_seed: u64) {
+        let seed = self.seed.unwrap_or(global_seed);
+        // Mixed rather than added, so neighbouring seeds are not the same stream shifted in time
+        let step_seed = mix(seed) ^ generation;
+        let mut rng = StdRng::seed_from_u64(step_seed);
+        match self.kind {
+            NoiseKind::Uniform => field.iter_mut().for_each(|v| *v += rng.gen_range(-1.0..1.0) * amplitude),
+            NoiseKind::Gaussian => field.iter_mut().for_each(|v| *v += gaussian(&mut rng) * amplitude),
+            NoiseKind::Perlin => {
+                let noise = perlin_field(width, height, self.scale, step_seed);
+                field.iter_mut().zip(noise).for_each(|(v, n)| *v += n * amplitude);
+            }
+            NoiseKind::Ou => {
+                // Start from the stationary distribution so there is no warm-up
+                if self.ou.len() != field.len() {
+                    let mut start = StdRng::seed_from_u64(seed);
+                    self.ou = (0..field.len()).map(|_| gaussian(&mut start)).collect();
+                }
+                // Exact update over one generation, which keeps the variance at 1
+                let decay = (-1.0 / self.tau).exp();
+                let kick = (1.0 - decay * decay).sqrt();
+                for (v, x) in field.iter_mut().zip(self.ou.iter_mut()) {
+                    *x = *x * decay + kick * gaussian(&mut rng);
+                    *v += *x * amplitude;
+                }
+            }
+            NoiseKind::Drops => {
+                let count = self.rate.floor() as usize + rng.gen_bool(self.rate.fract()) as usize;
+                self.drop_into(field, width, height, &mut rng, count);
+            }
+        }
+    }
+
+    // A whole field drawn from the model, for starting a run
+    pub fn initial(&self, width: u32, height: u32, global_seed: u64) -> Vec<f64> {
+        let seed = self.seed.unwrap_or(global_seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let len = (width * height) as usize;
+        match self.kind {
+            NoiseKind::Uniform => (0..len).map(|_| rng.gen()).collect(),
+            NoiseKind::Gaussian | NoiseKind::Ou => (0..len).map(|_| (0.5 + 0.2 * gaussian(&mut rng)).clamp(0.0, 1.0)).collect(),
+            NoiseKind::Perlin => perlin_field(width, height, self.scale, seed).into_iter().map(|n| (0.5 + 0.5 * n).clamp(0.0, 1.0)).collect(),
+            NoiseKind::Drops => {
+                let mut field = vec![0.0; len];
+                let count = (self.rate * INITIAL_DROP_GENERATIONS).round().max(1.0) as usize;
+                self.drop_into(&mut field, width, height, &mut rng, count);
+                field
+            }
+        }
+    }
+
+    fn drop_into(&self, field: &mut [f64], width: u32, height: u32, rng: &mut StdRng, count: usize) {
+        let (w, h) = (width as i32, height as i32);
+        for _ in 0..count {
+            let (cx, cy) = (rng.gen_range(0..w), rng.gen_range(0..h));
+            if let Some((_, pattern)) = &self.pattern {
+                stamp(field, width, height, pattern, cx, cy);
+                continue;
+            }
+            let reach = (self.radius * 3.0).ceil() as i32;
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    let (x, y) = ((cx + dx).rem_euclid(w), (cy + dy).rem_euclid(h));
+                    field[(y * w + x) as usize] += bell(((dx * dx + dy * dy) as f64).sqrt(), 0.0, self.radius);
+                }
+            }
+        }
+    }
+}
+
+// Standard normal by the Box-Muller transform
+fn gaussian(rng: &mut StdRng) -> f64 {
+    let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (TAU * v).cos()
+}
+
+// SplitMix64 finaliser, for lattice gradients that need no table
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Gradient noise in roughly ±1. The lattice is fitted to a whole number of
+// squares across the field, so the noise tiles when the edges wrap
+pub fn perlin_field(width: u32, height: u32, scale: f64, seed: u64) -> Vec<f64> {
+    let periods_x = ((width as f64 / scale).round() as u64).max(1);
+    let periods_y = ((height as f64 / scale).round() as u64).max(1);
+    let gradient = |ix: u64, iy: u64| {
+        let hash = mix(seed ^ mix((ix % periods_x) ^ mix(iy % periods_y)));
+        let angle = (hash >> 11) as f64 / (1u64 << 53) as f64 * TAU;
+        (angle.cos(), angle.sin())
+    };
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    (0..(width * height) as usize)
+        .map(|i| {
+            let u = ((i % width as usize) as f64 + 0.5) * periods_x as f64 / width as f64;
+            let v = ((i / width as usize) as f64 + 0.5) * periods_y as f64 / height as f64;
+            let (ix, iy) = (u.floor() as u64, v.floor() as u64);
+            let (fx, fy) = (u.fract(), v.fract());
+            let corner = |dx: u64, dy: u64| {
+                let (gx, gy) = gradient(ix + dx, iy + dy);
+                gx * (fx - dx as f64) + gy * (fy - dy as f64)
+            };
+            let (sx, sy) = (fade(fx), fade(fy));
+            let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * sx;
+            let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * sx;
+            (top + (bottom - top) * sy) * SQRT_2
+        })
+        .collect()
+}
+
+impl GameOfLife {
+    // Shift+N: next noise model
+    pub fn cycle_noise_model(&mut self) {
+        self.noise.cycle();
+    }
+
+    // Ctrl+N: replace the field with a sample of the current noise model
+    pub fn fill_with_noise(&mut self) {
+        self.record_history();
+        self.pxl_vec = self.noise.initial(self.a_width, self.a_height, self.noise_seed);
+        self.restart_from_field();
+    }
+}
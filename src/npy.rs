@@ -0,0 +1,221 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use crate::game::GameOfLife;
+use crate::image_io::resample;
+use crate::param_map::{Layer, MapKind};
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+const STACK_HEADER_LEN: usize = 128; // Room for any frame count, so the header can be rewritten in place
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NpyDtype {
+    F64,
+    F32,
+}
+
+impl NpyDtype {
+    fn descr(self) -> &'static str {
+        match self {
+            NpyDtype::F64 => "<f8",
+            NpyDtype::F32 => "<f4",
+        }
+    }
+}
+
+// Version 1.0 header, padded with spaces so the data starts on a multiple
+// of 64 bytes (or exactly at `min_len`) as numpy expects
+fn header(shape: &[usize], dtype: NpyDtype, min_len: usize) -> Vec<u8> {
+    let dims: Vec<String> = shape.iter().map(|d| d.to_string()).collect();
+    let shape = if dims.len() == 1 { format!("({},)", dims[0]) } else { format!("({})", dims.join(", ")) };
+    let mut dict = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", dtype.descr(), shape);
+    let prefix = NPY_MAGIC.len() + 4;
+    let total = (prefix + dict.len() + 1).div_ceil(64).max(1) * 64;
+    let total = total.max(min_len);
+    dict += &" ".repeat(total - prefix - dict.len() - 1);
+    dict.push('\n');
+    let mut out = NPY_MAGIC.to_vec();
+    out.extend_from_slice(&[1, 0]);
+    out.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    out.extend_from_slice(dict.as_bytes());
+    out
+}
+
+fn write_values(out: &mut impl Write, values: &[f64], dtype: NpyDtype) -> io::Result<()> {
+    for &v in values {
+        match dtype {
+            NpyDtype::F64 => out.write_all(&v.to_le_bytes())?,
+            NpyDtype::F32 => out.write_all(&(v as f32).to_le_bytes())?,
+        }
+    }
+    Ok(())
+}
+
+// Row major, so a field is written with shape (a_height, a_width)
+pub fn write_npy(path: &str, shape: &[usize], values: &[f64], dtype: NpyDtype) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&header(shape, dtype, 0))?;
+    write_values(&mut out, values, dtype)?;
+    out.flush()
+}
+
+// Value of a header dict key, up to the next top-level comma or brace
+fn header_value<'a>(dict: &'a str, key: &str) -> Option<&'a str> {
+    let start = dict.find(&format!("'{}'", key))? + key.len() + 2;
+    let rest = dict[start..].trim_start().strip_prefix(':')?.trim_start();
+    let end = if rest.starts_with('(') { rest.find(')')? + 1 } else { rest.find([',', '}'])? };
+    Some(rest[..end].trim())
+}
+
+// Shape and values, in C order, of a float64 or float32 array of either
+// byte order and either memory order
+pub fn read_npy(path: &str) -> Result<(Vec<usize>, Vec<f64>), String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let bad = |what: &str| format!("{}: {}", path, what);
+    if !data.starts_with(NPY_MAGIC) || data.len() < 10 {
+        return Err(bad("not a .npy file"));
+    }
+    // Version 1 has a 2-byte header length, versions 2 and 3 a 4-byte one
+    let (len, start) = match data[6] {
+        1 => (u16::from_le_bytes([data[8], data[9]]) as usize, 10),
+        2 | 3 if data.len() >= 12 => (u32::from_le_bytes([data[8], data[9], data[10], data[11]]) as usize, 12),
+        _ => return Err(bad("unsupported .npy version")),
+    };
+    let dict = std::str::from_utf8(data.get(start..start + len).ok_or(bad("header runs past the end"))?).map_err(|_| bad("bad header"))?;
+    let descr = header_value(dict, "descr").ok_or(bad("header has no descr"))?.trim_matches(['\'', '"']);
+    let fortran = header_value(dict, "fortran_order").ok_or(bad("header has no fortran_order"))? == "True";
+    let shape_text = header_value(dict, "shape").ok_or(bad("header has no shape"))?;
+    let shape: Result<Vec<usize>, _> =
+        shape_text.trim_matches(['(', ')']).split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::parse).collect();
+    let shape = shape.map_err(|_| bad("bad shape"))?;
+
+    let (size, big_endian) = match descr {
+        "<f8" | "=f8" => (8, false),
+        ">f8" => (8, true),
+        "<f4" | "=f4" => (4, false),
+        ">f4" => (4, true),
+        _ => return Err(bad(&format!("unsupported dtype '{}', use float64 or float32", descr))),
+    };
+    // The header is untrusted, so a shape too big to address is an error rather than an overflow
+    let count = shape.iter().try_fold(1usize, |n, &d| n.checked_mul(d)).ok_or(bad("shape too large"))?;
+    let end = count.checked_mul(size).and_then(|n| n.checked_add(start + len)).ok_or(bad("shape too large"))?;
+    let body = data.get(start + len..end).ok_or(bad("too few values"))?;
+    let values: Vec<f64> = body
+        .chunks(size)
+        .map(|b| match (size, big_endian) {
+            (8, false) => f64::from_le_bytes(b.try_into().unwrap_or([0; 8])),
+            (8, true) => f64::from_be_bytes(b.try_into().unwrap_or([0; 8])),
+            (_, false) => f32::from_le_bytes(b.try_into().unwrap_or([0; 4])) as f64,
+            (_, true) => f32::from_be_bytes(b.try_into().unwrap_or([0; 4])) as f64,
+        })
+        .collect();
+    if !fortran || shape.len() < 2 {
+        return Ok((shape, values));
+    }
+    // Fortran order: the first index varies fastest
+    let strides: Vec<usize> = shape.iter().scan(1, |stride, &d| Some(std::mem::replace(stride, *stride * d))).collect();
+    let mut c_order = vec![0.0; count];
+    for (i, v) in c_order.iter_mut().enumerate() {
+        let (mut rest, mut index) = (i, 0);
+        for (axis, &d) in shape.iter().enumerate().rev() {
+            index += (rest % d) * strides[axis];
+            rest /= d;
+        }
+        *v = values[index];
+    }
+    Ok((shape, c_order))
+}
+
+// One (frames, height, width) array written a frame at a time. The header is
+// rewritten with the real frame count when the stack is finished
+pub struct NpyStack {
+    out: BufWriter<File>,
+    width: usize,
+    height: usize,
+    dtype: NpyDtype,
+    frames: usize,
+}
+
+impl NpyStack {
+    pub fn create(path: &str, width: usize, height: usize, dtype: NpyDtype) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&header(&[0, height, width], dtype, STACK_HEADER_LEN))?;
+        Ok(Self { out, width, height, dtype, frames: 0 })
+    }
+
+    pub fn push(&mut self, field: &[f64]) -> io::Result<()> {
+        if field.len() != self.width * self.height {
+            let message = format!("frame of {} values in a {}x{} stack", field.len(), self.width, self.height);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+        self.frames += 1;
+        write_values(&mut self.out, field, self.dtype)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&header(&[self.frames, self.height, self.width], self.dtype, STACK_HEADER_LEN))?;
+        self.out.flush()
+    }
+}
+
+// Default file for each layer, used by the hotkeys
+pub fn layer_npy_path(layer: Layer) -> String {
+    match layer {
+        Layer::Field => "field.npy".to_string(),
+        Layer::Map(kind) => format!("map_{}.npy", kind.name()),
+        Layer::Mask => "mask.npy".to_string(),
+    }
+}
+
+impl GameOfLife {
+    pub fn save_npy(&self, layer: Layer, path: &str, dtype: NpyDtype) -> Result<(), String> {
+        let shape = [self.a_height as usize, self.a_width as usize];
+        let values = match layer {
+            Layer::Field => &self.pxl_vec[..],
+            Layer::Map(kind) => self.param_maps.get(kind, self.pxl_vec.len()).ok_or(format!("there is no {} map", kind.name()))?,
+            Layer::Mask => return Err("the mask has no .npy form".to_string()),
+        };
+        write_npy(path, &shape, values, dtype).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // A (height, width) array, resampled when its size differs from the
+    // field's. Flat arrays of the right length are taken as they are, and of
+    // a stack of frames only the last is used
+    pub fn load_npy(&mut self, layer: Layer, path: &str) -> Result<(), String> {
+        let (shape, values) = read_npy(path)?;
+        let (a_width, a_height) = (self.a_width as usize, self.a_height as usize);
+        let values = match shape[..] {
+            [n] if n == a_width * a_height => values,
+            [.., h, w] if h > 0 && w > 0 && values.len() >= h * w => {
+                let last = &values[values.len() - h * w..];
+                if (w, h) == (a_width, a_height) { last.to_vec() } else { resample(last, w, h, a_width, a_height) }
+            }
+            [.., h, w] if h > 0 && w > 0 => return Err(format!("{}: shape {:?} holds no complete frame", path, shape)),
+            _ => return Err(format!("{}: cannot fit shape {:?} to {}x{}", path, shape, a_width, a_height)),
+        };
+        match layer {
+            Layer::Field => {
+                self.record_history();
+                self.pxl_vec = values.into_iter().map(|v| v.clamp(0.0, 1.0)).collect();
+                self.restart_from_field();
+            }
+            Layer::Map(kind) => {
+                self.record_history();
+                self.param_maps.set(kind, values);
+            }
+            Layer::Mask => return Err("the mask has no .npy form".to_string()),
+        }
+        Ok(())
+    }
+}
+
+// "FILE" for the field or "m=FILE", "s=FILE", "dt=FILE" for a map
+pub fn parse_layer_path(text: &str) -> Result<(Layer, String), String> {
+    match text.split_once('=') {
+        Some((name, path)) => {
+            let kind = MapKind::from_name(name).ok_or(format!("unknown map '{}'", name))?;
+            Ok((Layer::Map(kind), path.to_string()))
+        }
+        None => Ok((Layer::Field, text.to_string())),
+    }
+}
@@ -0,0 +1,141 @@
+use crate::game::GameOfLife;
+use crate::image_io::{read_gray, resample};
+
+// Map value that leaves the global parameter as it is
+pub const NEUTRAL: f64 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MapKind {
+    M,
+    S,
+    Dt,
+}
+
+impl MapKind {
+    pub const ALL: [MapKind; 3] = [MapKind::M, MapKind::S, MapKind::Dt];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MapKind::M => "m",
+            MapKind::S => "s",
+            MapKind::Dt => "dt",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.name() == name)
+    }
+}
+
+// What the brush paints and the main view shows
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Layer {
+    Field,
+    Map(MapKind),
+    Mask,
+}
+
+impl Layer {
+    pub fn cycle(self) -> Self {
+        match self {
+            Layer::Field => Layer::Map(MapKind::M),
+            Layer::Map(MapKind::M) => Layer::Map(MapKind::S),
+            Layer::Map(MapKind::S) => Layer::Map(MapKind::Dt),
+            Layer::Map(MapKind::Dt) => Layer::Mask,
+            Layer::Mask => Layer::Field,
+        }
+    }
+}
+
+// Optional per-cell multipliers of bell_m, bell_s and dt. A cell with map
+// value v runs at 2v times the global value, so painting 0.5 changes nothing
+// and the sliders and modulation still move the whole world
+#[derive(Clone, Default)]
+pub struct ParamMaps {
+    maps: [Option<Vec<f64>>; 3],
+}
+
+impl ParamMaps {
+    // The map, if there is one that fits a field of `len` cells
+    pub fn get(&self, kind: MapKind, len: usize) -> Option<&[f64]> {
+        self.maps[kind as usize].as_deref().filter(|map| map.len() == len)
+    }
+
+    // The map to paint into, created neutral when missing or the wrong size
+    pub fn get_mut(&mut self, kind: MapKind, len: usize) -> &mut Vec<f64> {
+        let map = &mut self.maps[kind as usize];
+        if map.as_ref().is_none_or(|m| m.len() != len) {
+            *map = Some(vec![NEUTRAL; len]);
+        }
+        map.get_or_insert_with(Vec::new)
+    }
+
+    pub fn set(&mut self, kind: MapKind, values: Vec<f64>) {
+        self.maps[kind as usize] = Some(values);
+    }
+
+    pub fn clear(&mut self, kind: MapKind) {
+        self.maps[kind as usize] = None;
+    }
+
+    pub fn clear_all(&mut self) {
+        self.maps = Default::default();
+    }
+
+    // Memory held, for the undo history's byte cap
+    pub fn bytes(&self) -> usize {
+        self.maps.iter().flatten().map(|m| m.len() * size_of::<f64>()).sum()
+    }
+}
+
+// Multiplier of the global parameter for a cell with map value v
+pub fn factor(v: f64) -> f64 {
+    2.0 * v
+}
+
+impl GameOfLife {
+    // Gray levels of the image, stretched over the field
+    pub fn load_param_map(&mut self, kind: MapKind, path: &str) -> Result<(), String> {
+        let (width, height, gray) = read_gray(path)?;
+        let (a_width, a_height) = (self.a_width as usize, self.a_height as usize);
+        self.record_history();
+        self.param_maps.set(kind, resample(&gray, width, height, a_width, a_height));
+        Ok(())
+    }
+
+    // L: field -> m map -> s map -> dt map -> mask
+    pub fn cycle_layer(&mut self) {
+        self.layer = self.layer.cycle();
+    }
+
+    // D: forget the map or mask being shown
+    pub fn clear_layer(&mut self) {
+        match self.layer {
+            Layer::Field => {}
+            Layer::Map(kind) => {
+                self.record_history();
+                self.param_maps.clear(kind);
+            }
+            Layer::Mask => {
+                self.record_history();
+                self.mask = None;
+            }
+        }
+    }
+
+    pub fn layer_status(&self) -> String {
+        let len = self.pxl_vec.len();
+        let maps: Vec<&str> = MapKind::ALL.into_iter().filter(|&k| self.param_maps.get(k, len).is_some()).map(MapKind::name).collect();
+        let layer = match self.layer {
+            Layer::Field => "field".to_string(),
+            Layer::Map(kind) => format!("{} map", kind.name()),
+            Layer::Mask => format!("mask, painting {:?}", self.mask_brush),
+        };
+        format!(
+            "Layer: {}  Maps: {}  Mask: {}",
+            layer,
+            if maps.is_empty() { "none".to_string() } else { maps.join(" ") },
+            if self.mask_cells().is_some() { "on" } else { "off" }
+        )
+    }
+}
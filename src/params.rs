@@ -0,0 +1,78 @@
+use crate::game::GameOfLife;
+
+// A simulation parameter that can be changed by name, e.g. from keys,
+// modulation routes or batch runs
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub min: f64,
+    pub max: f64,
+}
+
+pub const PARAMETERS: [ParamSpec; 12] = [
+    ParamSpec { name: "update_freq", min: 1.0, max: 100.0 },
+    ParamSpec { name: "dt", min: 0.001, max: 1.0 },
+    ParamSpec { name: "kernel_rad", min: 1.0, max: 20.0 },
+    ParamSpec { name: "bell_m", min: 0.01, max: 1.0 },
+    ParamSpec { name: "bell_s", min: 0.01, max: 1.0 },
+    ParamSpec { name: "noise_intensity", min: 0.0, max: 1.0 },
+    ParamSpec { name: "info_bar_height", min: 0.0, max: 200.0 },
+    ParamSpec { name: "grain_density", min: 0.0, max: 200.0 },
+    ParamSpec { name: "grain_duration", min: 0.005, max: 0.5 },
+    ParamSpec { name: "grain_pitch", min: 0.0, max: 1.0 },
+    ParamSpec { name: "grain_speed", min: 0.0, max: 4.0 },
+    ParamSpec { name: "grain_gain", min: 0.0, max: 1.0 },
+];
+
+pub fn param_spec(name: &str) -> Option<&'static ParamSpec> {
+    PARAMETERS.iter().find(|p| p.name == name)
+}
+
+impl GameOfLife {
+    pub fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "update_freq" => Some(self.update_freq),
+            "dt" => Some(self.dt),
+            "kernel_rad" => Some(self.kernel_rad as f64),
+            "bell_m" => Some(self.bell_m),
+            "bell_s" => Some(self.bell_s),
+            "noise_intensity" => Some(self.noise_intensity),
+            "info_bar_height" => Some(self.info_bar_height as f64),
+            "grain_density" => Some(self.granular.density),
+            "grain_duration" => Some(self.granular.duration),
+            "grain_pitch" => Some(self.granular.pitch),
+            "grain_speed" => Some(self.granular.speed),
+            "grain_gain" => Some(self.granular.gain),
+            _ => None,
+        }
+    }
+
+    // Clamps to the parameter's range; returns false for unknown names
+    pub fn set_parameter(&mut self, name: &str, value: f64) -> bool {
+        let Some(spec) = param_spec(name) else {
+            return false;
+        };
+        let value = value.clamp(spec.min, spec.max);
+        match name {
+            "update_freq" => {
+                self.update_freq = value;
+                self.dt = 1.0 / self.update_freq;
+            }
+            "dt" => {
+                self.dt = value;
+                self.update_freq = (1.0 / self.dt).clamp(1.0, 100.0);
+            }
+            "kernel_rad" => self.kernel_rad = value.round() as u32,
+            "bell_m" => self.bell_m = value,
+            "bell_s" => self.bell_s = value,
+            "noise_intensity" => self.noise_intensity = value,
+            "info_bar_height" => self.info_bar_height = value as u32,
+            "grain_density" => self.granular.density = value,
+            "grain_duration" => self.granular.duration = value,
+            "grain_pitch" => self.granular.pitch = value,
+            "grain_speed" => self.granular.speed = value,
+            "grain_gain" => self.granular.gain = value,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::Write;
+use std::fs;
+use crate::game::GameOfLife;
+use crate::params::param_spec;
+use crate::image_io::resample;
+
+pub const PATTERN_EXTENSION: &str = "pattern";
+const PATTERN_HEADER: &str = "lenia-pattern 1";
+
+// A patch of cells together with the parameters it lives under. Stored as
+// text: a header, "# ..." comments, "name", "param NAME VALUE" and "size W H"
+// lines, then "cells" followed by one line of values per row
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pub name: String,
+    pub comments: Vec<String>,
+    pub params: Vec<(String, f64)>,
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<f64>,
+}
+
+impl Pattern {
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        // Writing to a String cannot fail
+        let _ = writeln!(out, "{}", PATTERN_HEADER);
+        for comment in &self.comments {
+            let _ = writeln!(out, "# {}", comment);
+        }
+        let _ = writeln!(out, "name {}", self.name);
+        for (name, value) in &self.params {
+            let _ = writeln!(out, "param {} {}", name, value);
+        }
+        let _ = writeln!(out, "size {} {}", self.width, self.height);
+        let _ = writeln!(out, "cells");
+        for row in self.cells.chunks(self.width.max(1)) {
+            let values: Vec<String> = row.iter().map(|v| format!("{:.4}", v)).collect();
+            let _ = writeln!(out, "{}", values.join(" "));
+        }
+        out
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next().map(str::trim) != Some(PATTERN_HEADER) {
+            return Err("not a pattern file".to_string());
+        }
+        let mut pattern = Pattern { name: String::new(), comments: Vec::new(), params: Vec::new(), width: 0, height: 0, cells: Vec::new() };
+        let bad = |line: &str| format!("bad pattern line '{}'", line);
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if let Some(comment) = line.strip_prefix('#') {
+                pattern.comments.push(comment.trim().to_string());
+                continue;
+            }
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "" => {}
+                "name" => pattern.name = rest.to_string(),
+                "param" => {
+                    let (name, value) = rest.split_once(' ').ok_or(bad(line))?;
+                    if param_spec(name).is_none() {
+                        return Err(format!("unknown parameter '{}'", name));
+                    }
+                    pattern.params.push((name.to_string(), value.parse().map_err(|_| bad(line))?));
+                }
+                "size" => {
+                    let (w, h) = rest.split_once(' ').ok_or(bad(line))?;
+                    pattern.width = w.parse().map_err(|_| bad(line))?;
+                    pattern.height = h.parse().map_err(|_| bad(line))?;
+                }
+                "cells" => {
+                    let cells: Result<Vec<f64>, String> = lines
+                        .by_ref()
+                        .flat_map(str::split_whitespace)
+                        .map(|t| t.parse::<f64>().map(|v| v.clamp(0.0, 1.0)).map_err(|_| format!("bad cell value '{}'", t)))
+                        .collect();
+                    pattern.cells = cells?;
+                    break;
+                }
+                _ => return Err(bad(line)),
+            }
+        }
+        if pattern.cells.len() != pattern.width * pattern.height {
+            return Err(format!("pattern has {} cells, expected {}x{}", pattern.cells.len(), pattern.width, pattern.height));
+        }
+        Ok(pattern)
+    }
+
+    // Flipped, then turned clockwise by quarter turns, then scaled. Scaling
+    // scales the kernel radius with it, so a creature keeps its behaviour
+    pub fn transformed(&self, quarter_turns: u8, flip_x: bool, flip_y: bool, scale: f64) -> Pattern {
+        let (w, h) = (self.width, self.height);
+        let at = |x: usize, y: usize| {
+            let x = if flip_x { w - 1 - x } else { x };
+            let y = if flip_y { h - 1 - y } else { y };
+            self.cells[y * w + x]
+        };
+        let (mut cells, mut width, mut height) = (Vec::with_capacity(w * h), w, h);
+        match quarter_turns % 4 {
+            0 => (0..h).for_each(|y| (0..w).for_each(|x| cells.push(at(x, y)))),
+            1 => {
+                (width, height) = (h, w);
+                (0..w).for_each(|y| (0..h).for_each(|x| cells.push(at(y, h - 1 - x))));
+            }
+            2 => (0..h).for_each(|y| (0..w).for_each(|x| cells.push(at(w - 1 - x, h - 1 - y)))),
+            _ => {
+                (width, height) = (h, w);
+                (0..w).for_each(|y| (0..h).for_each(|x| cells.push(at(w - 1 - y, x))));
+            }
+        }
+        let mut params = self.params.clone();
+        if scale != 1.0 && width > 0 && height > 0 {
+            let (new_width, new_height) = (((width as f64 * scale).round() as usize).max(1), ((height as f64 * scale).round() as usize).max(1));
+            cells = resample(&cells, width, height, new_width, new_height);
+            (width, height) = (new_width, new_height);
+            for (_, value) in params.iter_mut().filter(|(name, _)| name == "kernel_rad") {
+                *value = (*value * scale).round().max(1.0);
+            }
+        }
+        Pattern { name: self.name.clone(), comments: self.comments.clone(), params, width, height, cells }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+// Copy the cells of the pattern centred on (cx, cy) into a width x height
+// field, replacing what was there. Cells past the edge wrap around
+pub fn stamp(field: &mut [f64], width: u32, height: u32, pattern: &Pattern, cx: i32, cy: i32) {
+    let (w, h) = (width as i32, height as i32);
+    for py in 0..pattern.height {
+        for px in 0..pattern.width {
+            let x = (cx + px as i32 - pattern.width as i32 / 2).rem_euclid(w);
+            let y = (cy + py as i32 - pattern.height as i32 / 2).rem_euclid(h);
+            field[(y * w + x) as usize] = pattern.cells[py * pattern.width + px];
+        }
+    }
+}
+
+impl GameOfLife {
+    pub fn stamp_pattern(&mut self, pattern: &Pattern, cx: i32, cy: i32) {
+        stamp(&mut self.pxl_vec, self.a_width, self.a_height, pattern, cx, cy);
+    }
+
+    pub fn apply_pattern_params(&mut self, pattern: &Pattern) {
+        for (name, value) in &pattern.params {
+            self.set_parameter(name, *value);
+        }
+    }
+
+    // Clear the field and put the pattern in the middle under its own parameters
+    pub fn load_pattern(&mut self, pattern: &Pattern) {
+        self.record_history();
+        self.pxl_vec.iter_mut().for_each(|v| *v = 0.0);
+        self.apply_pattern_params(pattern);
+        self.stamp_pattern(pattern, self.a_width as i32 / 2, self.a_height as i32 / 2);
+        self.restart_from_field();
+    }
+}
@@ -0,0 +1,154 @@
+use fft2d::slice::{fft_2d, ifft_2d};
+use num_complex::Complex;
+use rayon::prelude::*;
+use crate::game::GameOfLife;
+
+pub const MAX_PERIOD: usize = 64;
+pub const DETECT_INTERVAL: u64 = 16; // Generations between checks in the viewer
+const REPEAT_ERROR: f64 = 1e-3; // Relative squared error that still counts as a repeat
+const MOVING_SPEED: f64 = 0.01; // Cells per generation
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PeriodTarget {
+    Field,
+    Creature, // The heaviest tracked creature
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Periodicity {
+    pub period: usize,
+    pub dx: f64, // Displacement per period, in cells
+    pub dy: f64,
+    pub error: f64,
+}
+
+impl Periodicity {
+    pub fn speed(&self) -> f64 {
+        self.dx.hypot(self.dy) / self.period as f64
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.speed() > MOVING_SPEED
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} period {}  shift ({:.2}, {:.2})  speed {:.3} c/gen",
+            if self.is_moving() { "glider" } else { "oscillator" },
+            self.period, self.dx, self.dy, self.speed()
+        )
+    }
+}
+
+fn spectrum(frame: &[f64], w: usize, h: usize) -> Vec<Complex<f64>> {
+    let mut buffer: Vec<Complex<f64>> = frame.iter().map(|&v| Complex::new(v, 0.0)).collect();
+    fft_2d(w, h, &mut buffer);
+    buffer
+}
+
+// Peak of a 3-point parabola through the samples around the maximum
+fn subpixel(before: f64, peak: f64, after: f64) -> f64 {
+    let denom = before - 2.0 * peak + after;
+    if denom.abs() < 1e-12 { 0.0 } else { (0.5 * (before - after) / denom).clamp(-0.5, 0.5) }
+}
+
+// Translation that maps `past` onto `current`, by phase correlation
+fn translation(current: &[Complex<f64>], past: &[f64], w: usize, h: usize) -> (f64, f64) {
+    let past = spectrum(past, w, h);
+    // Both spectra are transposed the same way, so the product is too and
+    // the inverse transform of the transposed layout comes back row major
+    let mut cross: Vec<Complex<f64>> = current
+        .iter()
+        .zip(&past)
+        .map(|(a, b)| {
+            let c = a * b.conj();
+            c / (c.norm() + 1e-12)
+        })
+        .collect();
+    ifft_2d(h, w, &mut cross);
+
+    let (peak, _) = cross.iter().enumerate().fold((0, f64::MIN), |best, (i, c)| if c.re > best.1 { (i, c.re) } else { best });
+    let (px, py) = (peak % w, peak / w);
+    let at = |x: usize, y: usize| cross[(y % h) * w + x % w].re;
+    let fx = subpixel(at(px + w - 1, py), at(px, py), at(px + 1, py));
+    let fy = subpixel(at(px, py + h - 1), at(px, py), at(px, py + 1));
+    // Shifts past the half-way point are negative
+    let unwrap = |p: usize, n: usize| if p > n / 2 { p as f64 - n as f64 } else { p as f64 };
+    (unwrap(px, w) + fx, unwrap(py, h) + fy)
+}
+
+// Relative squared difference between `current` and `past` moved by (dx, dy)
+fn repeat_error(current: &[f64], past: &[f64], w: usize, h: usize, dx: f64, dy: f64) -> f64 {
+    let sample = |x: f64, y: f64| {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let at = |x: f64, y: f64| past[(y as i64).rem_euclid(h as i64) as usize * w + (x as i64).rem_euclid(w as i64) as usize];
+        let top = at(x0, y0) * (1.0 - tx) + at(x0 + 1.0, y0) * tx;
+        let bottom = at(x0, y0 + 1.0) * (1.0 - tx) + at(x0 + 1.0, y0 + 1.0) * tx;
+        top * (1.0 - ty) + bottom * ty
+    };
+    let (mut diff, mut norm) = (0.0, 0.0);
+    for (i, &v) in current.iter().enumerate() {
+        let (x, y) = ((i % w) as f64, (i / w) as f64);
+        diff += (v - sample(x - dx, y - dy)).powi(2);
+        norm += v * v;
+    }
+    if norm > 0.0 { diff / norm } else { f64::INFINITY }
+}
+
+// The shortest lag at which the newest frame repeats an older one up to a
+// translation. frames[0] is the newest, frames[p] is p generations earlier
+pub fn detect(frames: &[Vec<f64>], w: usize, h: usize) -> Option<Periodicity> {
+    let current = frames.first()?;
+    let current_spectrum = spectrum(current, w, h);
+    let candidates: Vec<Periodicity> = (1..frames.len().min(MAX_PERIOD + 1))
+        .into_par_iter()
+        .map(|period| {
+            let (dx, dy) = translation(&current_spectrum, &frames[period], w, h);
+            let error = repeat_error(current, &frames[period], w, h, dx, dy);
+            Periodicity { period, dx, dy, error }
+        })
+        .collect();
+    candidates.into_iter().find(|c| c.error < REPEAT_ERROR)
+}
+
+fn crop(frame: &[f64], a_width: u32, a_height: u32, x0: i64, y0: i64, side: usize) -> Vec<f64> {
+    let (w, h) = (a_width as i64, a_height as i64);
+    (0..side * side)
+        .map(|i| {
+            let x = (x0 + (i % side) as i64).rem_euclid(w);
+            let y = (y0 + (i / side) as i64).rem_euclid(h);
+            frame[(y * w + x) as usize]
+        })
+        .collect()
+}
+
+impl GameOfLife {
+    pub fn detect_periodicity(&self, target: PeriodTarget) -> Option<Periodicity> {
+        let frames = self.rewind.recent(MAX_PERIOD + 1);
+        match target {
+            PeriodTarget::Field => detect(&frames, self.a_width as usize, self.a_height as usize),
+            PeriodTarget::Creature => {
+                // A window around the creature, big enough that it stays inside
+                // while it moves over one period
+                let creature = self.tracker.creatures.iter().max_by(|a, b| a.mass.total_cmp(&b.mass))?;
+                let extent = (creature.max_x - creature.min_x).max(creature.max_y - creature.min_y) + 1.0;
+                let side = ((extent * 3.0) as usize).min(self.a_width.min(self.a_height) as usize).max(8);
+                let x0 = (creature.centroid_x - side as f64 / 2.0).floor() as i64;
+                let y0 = (creature.centroid_y - side as f64 / 2.0).floor() as i64;
+                let crops: Vec<Vec<f64>> = frames.iter().map(|f| crop(f, self.a_width, self.a_height, x0, y0, side)).collect();
+                detect(&crops, side, side)
+            }
+        }
+    }
+
+    // J: field -> creature -> off
+    pub fn cycle_period_target(&mut self) {
+        self.period_target = match self.period_target {
+            Some(PeriodTarget::Field) => Some(PeriodTarget::Creature),
+            Some(PeriodTarget::Creature) => None,
+            None => Some(PeriodTarget::Field),
+        };
+        self.periodicity = None;
+    }
+}
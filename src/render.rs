@@ -1,42 +1,133 @@
 use sdl2::pixels::Color;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
-use sdl2::rect::Rect;
+use sdl2::rect::{Point, Rect};
 use sdl2::ttf::Font;
 use sdl2::gfx::primitives::DrawRenderer;
 use sdl2::mouse::MouseButton;
 use sdl2::event::Event;
 use crate::game::GameOfLife;
+use crate::brush::BrushShape;
+use crate::stats::{Observables, SPARKLINE_LEN};
+use crate::midi::MidiAction;
+use crate::periodicity::MAX_PERIOD;
+use crate::param_map::{Layer, NEUTRAL};
+use crate::mask::CellType;
+
+// Parameters behind the info window sliders, top to bottom
+const SLIDER_PARAMS: [&str; 5] = ["update_freq", "kernel_rad", "bell_m", "bell_s", "noise_intensity"];
+
+type ObservableFn = fn(&Observables) -> f64;
 
 impl GameOfLife {
     pub fn draw(&self, canvas: &mut Canvas<Window>) {
         canvas.set_draw_color(Color::RGB(10, 20, 30));
         canvas.clear();
 
-        for (i, &val) in self.pxl_vec.iter().enumerate() {
-            let x = (i % self.a_width as usize) as i32 * self.pixel_edge_size as i32 + (self.pixel_edge_size / 2) as i32;
-            let y = (i / self.a_width as usize) as i32 * self.pixel_edge_size as i32 + (self.pixel_edge_size / 2) as i32;
-
-            let color_idx = (val * 255.0).clamp(0.0, 255.0) as usize;
-            let color = self.colors[color_idx];
-
-            if self.smooth_edges {
-                let radius = (self.pixel_edge_size as f32 * 0.5) as i16;
-                let _ = canvas.filled_circle(x as i16, y as i16, radius, color);
-            } else {
-                canvas.set_draw_color(color);
-                let _ = canvas.fill_rect(Rect::new(
-                    x - (self.pixel_edge_size / 2) as i32,
-                    y - (self.pixel_edge_size / 2) as i32,
-                    self.pixel_edge_size,
-                    self.pixel_edge_size,
-                ));
+        // Only visit the cells that fall inside the window
+        let (min_x, min_y) = self.camera.screen_to_cell(0, 0);
+        let (max_x, max_y) = self.camera.screen_to_cell(self.width as i32, self.height as i32);
+        let x_start = min_x.floor().max(0.0) as u32;
+        let y_start = min_y.floor().max(0.0) as u32;
+        let x_end = (max_x.ceil().max(0.0) as u32).min(self.a_width);
+        let y_end = (max_y.ceil().max(0.0) as u32).min(self.a_height);
+
+        // A parameter map is shown in gray, mid gray where it is neutral
+        let map = match self.layer {
+            Layer::Field | Layer::Mask => None,
+            Layer::Map(kind) => Some(self.param_maps.get(kind, self.pxl_vec.len())),
+        };
+        let mask = self.mask_cells();
+        for cy in y_start..y_end {
+            for cx in x_start..x_end {
+                let index = (cy * self.a_width + cx) as usize;
+                let color = match (mask.map(|m| m[index]), map) {
+                    // Masked cells always show their type, as does every cell on the mask layer
+                    (Some(CellType::Wall), _) => Color::RGB(110, 110, 120),
+                    (Some(CellType::Source), _) => Color::RGB(255, 240, 170),
+                    (Some(CellType::Sink), _) => Color::RGB(70, 30, 110),
+                    (_, None) if self.layer == Layer::Mask => Color::RGB(0, 0, 0),
+                    (_, None) => self.colors[(self.pxl_vec[index] * 255.0).clamp(0.0, 255.0) as usize],
+                    (_, Some(values)) => {
+                        let gray = (values.map_or(NEUTRAL, |v| v[index]) * 255.0).clamp(0.0, 255.0) as u8;
+                        Color::RGB(gray, gray, gray)
+                    }
+                };
+
+                // Cell edges are rounded separately so neighbours tile without gaps
+                let (x0, y0) = self.camera.cell_to_screen(cx as f64, cy as f64);
+                let (x1, y1) = self.camera.cell_to_screen((cx + 1) as f64, (cy + 1) as f64);
+                let (x0, y0, x1, y1) = (x0.floor() as i32, y0.floor() as i32, x1.floor() as i32, y1.floor() as i32);
+                if x1 <= x0 || y1 <= y0 {
+                    continue;
+                }
+
+                if self.smooth_edges {
+                    let radius = ((x1 - x0) as f32 * 0.5) as i16;
+                    let _ = canvas.filled_circle(((x0 + x1) / 2) as i16, ((y0 + y1) / 2) as i16, radius, color);
+                } else {
+                    canvas.set_draw_color(color);
+                    let _ = canvas.fill_rect(Rect::new(x0, y0, (x1 - x0) as u32, (y1 - y0) as u32));
+                }
             }
         }
 
+        if self.show_creatures {
+            self.draw_creatures(canvas);
+        }
+        if let Some(mode) = self.spectral {
+            let ((x0, y0), (x1, y1)) = self.spectral_line(mode);
+            let (x0, y0) = self.camera.cell_to_screen(x0, y0);
+            let (x1, y1) = self.camera.cell_to_screen(x1, y1);
+            let _ = canvas.line(x0 as i16, y0 as i16, x1 as i16, y1 as i16, Color::RGBA(120, 200, 255, 200));
+        }
+        if self.granular.enabled {
+            for head in &self.granular.heads {
+                let (x, y) = self.camera.cell_to_screen(head.x, head.y);
+                let _ = canvas.circle(x as i16, y as i16, (2.0 * self.camera.zoom).max(3.0) as i16, Color::RGBA(255, 200, 80, 220));
+            }
+        }
+        if self.library.open {
+            self.draw_library(canvas);
+        } else {
+            self.draw_brush_cursor(canvas);
+        }
+
         canvas.present();
     }
 
+    // Bounding box and ID of every tracked creature
+    fn draw_creatures(&self, canvas: &mut Canvas<Window>) {
+        let color = Color::RGBA(255, 80, 80, 200);
+        for creature in &self.tracker.creatures {
+            let (x0, y0) = self.camera.cell_to_screen(creature.min_x, creature.min_y);
+            let (x1, y1) = self.camera.cell_to_screen(creature.max_x + 1.0, creature.max_y + 1.0);
+            let _ = canvas.rectangle(x0 as i16, y0 as i16, x1 as i16, y1 as i16, color);
+            let (note, cents) = self.quantiser.midi_note(1.0 - creature.centroid_y / self.a_height as f64);
+            let label = format!("#{} m={:.0} v={:.2},{:.2} n={}{:+.0}c", creature.id, creature.mass, creature.velocity_x, creature.velocity_y, note, cents);
+            let _ = canvas.string(x0 as i16, y0 as i16 - 10, &label, color);
+        }
+    }
+
+    // Outline of the cells the brush would touch under the mouse
+    fn draw_brush_cursor(&self, canvas: &mut Canvas<Window>) {
+        let (cx, cy) = self.camera.screen_to_cell(self.mouse_pos.0, self.mouse_pos.1);
+        let (x, y) = self.camera.cell_to_screen(cx.floor() + 0.5, cy.floor() + 0.5);
+        let (x, y) = (x as i16, y as i16);
+        let radius = ((self.brush.radius as f64 + 0.5) * self.camera.zoom) as i16;
+        let color = Color::RGBA(255, 255, 255, 160);
+
+        let _ = match self.brush.shape {
+            BrushShape::Circle => canvas.circle(x, y, radius, color),
+            BrushShape::Square => canvas.rectangle(x - radius, y - radius, x + radius, y + radius, color),
+            BrushShape::Ring => {
+                let inner = ((self.brush.radius as f64 * 0.6 - 0.5).max(0.0) * self.camera.zoom) as i16;
+                let _ = canvas.circle(x, y, inner, color);
+                canvas.circle(x, y, radius, color)
+            }
+        };
+    }
+
     pub fn update_info_window(&mut self, font: &Font) {
         if let Some(info_window) = &self.info_window {
             let mut info_canvas = info_window.clone().into_canvas().build().unwrap();
@@ -46,7 +137,32 @@ impl GameOfLife {
             let texture_creator = info_canvas.texture_creator();
             let text_lines = vec![
                 format!("FPS: {:.2}", self.fps),
-                format!("Generation: {}", self.generation),
+                format!("Generation: {}  Speed: {}x  Rewind: -{}/{}", self.generation, self.speed, self.rewind.offset(), self.rewind.len()),
+                {
+                    let pos = self.clock.position(self.generation);
+                    format!("Clock: {:.1} BPM  {} steps/16th  Bar {} Beat {}.{}", self.clock.bpm, self.clock.steps_per_sixteenth, pos.bar + 1, pos.beat + 1, pos.sixteenth + 1)
+                },
+                format!("dt: {:.3}  Integrator: {:?}  Wrap: {}", self.dt, self.integrator, self.wrap_edges),
+                format!("Noise: {} {}", self.noise.spec(), if self.noise_enabled { "on" } else { "off" }),
+                format!("Init: {}", self.init.menu()),
+                format!("      {}", self.init.spec()),
+                if self.library.open { self.library.status() } else { "Library: closed (Tab)".to_string() },
+                format!("Creatures: {}  Voices: {}  {}", self.tracker.creatures.len(), self.synth.lock().map_or(0, |s| s.active_voices()), self.tracker.events.iter().take(3).map(|e| format!("{:?}", e)).collect::<Vec<_>>().join(" ")),
+                format!("Scale: {}  Synth: {}", self.quantiser.describe(), match self.spectral {
+                    Some(mode) => format!("spectral {:?}{}", mode, if self.follow_creature { " (follow)" } else { "" }),
+                    None => "voices".to_string(),
+                }),
+                format!("Modulation: {} routes  Track: {:.1}s", self.modulation.routes.len(), self.modulation.time),
+                format!("Sources: {}", self.modulation_sources()),
+                format!("Grains: {}  {} playing  density {:.0}/s  {:.0} ms", if self.granular.enabled { "on" } else { "off" }, self.synth.lock().map_or(0, |s| s.granular.active_grains()), self.granular.density, self.granular.duration * 1000.0),
+                self.midi_status(),
+                self.layer_status(),
+                match (self.period_target, &self.periodicity) {
+                    (None, _) => "Repeat: off".to_string(),
+                    (Some(target), Some(p)) => format!("Repeat ({:?}): {}", target, p.describe()),
+                    (Some(target), None) => format!("Repeat ({:?}): none within {} generations", target, MAX_PERIOD),
+                },
+                format!("Brush: {:?} r={} {:?} {:.2} {:?}", self.brush.shape, self.brush.radius, self.brush.mode, self.brush.value, self.brush.falloff),
             ];
 
             let mut y_offset = 10;
@@ -67,6 +183,7 @@ impl GameOfLife {
             }
 
             // Adding sliders for adjusting parameters
+            self.slider_y_start = y_offset;
             self.draw_slider(&mut info_canvas, &font, "Update Frequency", self.update_freq as f32, 1.0, 100.0, y_offset);
             y_offset += line_height;
             self.draw_slider(&mut info_canvas, &font, "Kernel Radius", self.kernel_rad as f32, 1.0, 20.0, y_offset);
@@ -76,6 +193,24 @@ impl GameOfLife {
             self.draw_slider(&mut info_canvas, &font, "Bell S", self.bell_s as f32, 0.01, 1.0, y_offset);
             y_offset += line_height;
             self.draw_slider(&mut info_canvas, &font, "Noise Intensity", self.noise_intensity as f32, 0.0, 1.0, y_offset);
+            y_offset += line_height;
+
+            // Live observables with their recent history
+            if let Some(obs) = self.stats.latest() {
+                let rows: [(String, ObservableFn); 7] = [
+                    (format!("Mass: {:.1}", obs.mass), |o| o.mass),
+                    (format!("Centroid: {:.1}, {:.1}", obs.centroid_x, obs.centroid_y), |o| o.centroid_x),
+                    (format!("Speed: {:.3}", obs.speed()), |o| o.speed()),
+                    (format!("Growth mean: {:.3}", obs.growth_mean), |o| o.growth_mean),
+                    (format!("Variance: {:.1}", obs.variance), |o| o.variance),
+                    (format!("Entropy: {:.3}", obs.entropy), |o| o.entropy),
+                    (format!("Live: {:.1}%", obs.live_fraction * 100.0), |o| o.live_fraction),
+                ];
+                for (label, value) in rows {
+                    self.draw_sparkline(&mut info_canvas, font, &label, &self.stats.series(value), y_offset);
+                    y_offset += line_height;
+                }
+            }
 
             info_canvas.present();
         }
@@ -106,21 +241,68 @@ impl GameOfLife {
         let _ = canvas.fill_rect(Rect::new(knob_x - 5, slider_y - 5, 10, 20));
     }
 
+    fn draw_sparkline(&self, canvas: &mut Canvas<Window>, font: &Font, label: &str, series: &[f64], y_offset: i32) {
+        let texture_creator = canvas.texture_creator();
+        let label_surface = font.render(label)
+            .blended(Color::RGB(255, 255, 255))
+            .map_err(|e| e.to_string()).unwrap();
+        let label_texture = texture_creator.create_texture_from_surface(&label_surface)
+            .map_err(|e| e.to_string()).unwrap();
+
+        let label_target = Rect::new(10, y_offset, label_surface.width(), label_surface.height());
+        let _ = canvas.copy(&label_texture, None, Some(label_target));
+
+        // Plot area, scaled to the min/max of the visible samples
+        let plot_x = 250;
+        let plot_y = y_offset + 2;
+        let plot_width = 240;
+        let plot_height = 22;
+        canvas.set_draw_color(Color::RGB(40, 40, 40));
+        let _ = canvas.fill_rect(Rect::new(plot_x, plot_y, plot_width, plot_height as u32));
+
+        if series.len() < 2 {
+            return;
+        }
+        let min = series.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = series.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let range = if max > min { max - min } else { 1.0 };
+        let points: Vec<Point> = series
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| {
+                let x = plot_x + (i as f64 / (SPARKLINE_LEN - 1) as f64 * (plot_width - 1) as f64) as i32;
+                let y = plot_y + plot_height - 1 - ((v - min) / range * (plot_height - 1) as f64) as i32;
+                Point::new(x, y)
+            })
+            .collect();
+        canvas.set_draw_color(Color::RGB(120, 200, 120));
+        let _ = canvas.draw_lines(points.as_slice());
+    }
+
     pub fn handle_slider_events(&mut self, event: &Event) {
         if let Some(_info_window) = &self.info_window {
             match *event {
                 Event::MouseButtonDown { x, y, mouse_btn: MouseButton::Left, .. } => {
                     // Check if the click is within the slider area
-                    let slider_y_offsets = [50, 80, 110, 140, 170]; // Example y_offsets for sliders
+                    let slider_y_offsets: Vec<i32> = (0..5).map(|i| self.slider_y_start + i * 30).collect();
                     let slider_x = 200;
                     let slider_width = 200;
                     let slider_height = 10;
 
                     for (i, &slider_y) in slider_y_offsets.iter().enumerate() {
                         if y >= slider_y + 10 && y <= slider_y + 10 + slider_height && x >= slider_x && x <= slider_x + slider_width {
+                            // In MIDI learn, clicking a slider picks its parameter as the target
+                            if self.midi_learn.is_some() {
+                                self.midi_learn = Some(MidiAction::Param(SLIDER_PARAMS[i].to_string()));
+                                continue;
+                            }
+                            self.record_history();
                             let new_value = ((x - slider_x) as f32 / slider_width as f32).clamp(0.0, 1.0);
                             match i {
-                                0 => self.update_freq = new_value as f64 * (100.0 - 1.0) + 1.0,
+                                0 => {
+                                    self.update_freq = new_value as f64 * (100.0 - 1.0) + 1.0;
+                                    self.dt = 1.0 / self.update_freq;
+                                },
                                 1 => self.kernel_rad = (new_value as f64 * (20.0 - 1.0) + 1.0).round() as u32,
                                 2 => self.bell_m = new_value as f64 * (1.0 - 0.01) + 0.01,
                                 3 => self.bell_s = new_value as f64 * (1.0 - 0.01) + 0.01,
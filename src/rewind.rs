@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+
+pub const DEFAULT_REWIND_FRAMES: usize = 200;
+
+// The last generations of the field, so playback can be scrubbed backwards
+pub struct RewindBuffer {
+    frames: VecDeque<(u64, Vec<f64>)>,
+    pub capacity: usize,
+    cursor: Option<usize>, // Frame being shown while scrubbing, None when live
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            capacity,
+            cursor: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.cursor = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    // How many generations back from the newest frame we are
+    pub fn offset(&self) -> usize {
+        self.cursor.map_or(0, |c| self.frames.len() - 1 - c)
+    }
+
+    pub fn push(&mut self, generation: u64, field: &[f64]) {
+        // Stepping from a scrubbed position forks history there
+        if let Some(cursor) = self.cursor.take() {
+            self.frames.truncate(cursor + 1);
+        }
+        self.frames.push_back((generation, field.to_vec()));
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    // Up to `count` frames ending at the newest, newest first. Stops early at
+    // a gap, so frame i is always exactly i generations old
+    pub fn recent(&self, count: usize) -> Vec<Vec<f64>> {
+        let mut frames = Vec::new();
+        let mut expected = self.frames.back().map(|(g, _)| *g);
+        for (generation, field) in self.frames.iter().rev().take(count) {
+            if Some(*generation) != expected {
+                break;
+            }
+            frames.push(field.clone());
+            expected = generation.checked_sub(1);
+        }
+        frames
+    }
+
+    pub fn back(&mut self, steps: usize) -> Option<&(u64, Vec<f64>)> {
+        let newest = self.frames.len().checked_sub(1)?;
+        let cursor = self.cursor.unwrap_or(newest).saturating_sub(steps);
+        self.cursor = Some(cursor);
+        self.frames.get(cursor)
+    }
+
+    // None once we are back at the newest frame and need to simulate
+    pub fn forward(&mut self, steps: usize) -> Option<&(u64, Vec<f64>)> {
+        let cursor = self.cursor? + steps;
+        if cursor + 1 >= self.frames.len() {
+            self.cursor = None;
+            return self.frames.back();
+        }
+        self.cursor = Some(cursor);
+        self.frames.get(cursor)
+    }
+}
@@ -0,0 +1,214 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use rand::{Rng, rngs::StdRng, SeedableRng};
+use rayon::prelude::*;
+use crate::batch::BatchTemplate;
+use crate::cli::Options;
+use crate::game::{DEFAULT_BELL_M, DEFAULT_BELL_S, DEFAULT_KERNEL_RAD, DEFAULT_SEED};
+use crate::pattern::{Pattern, PATTERN_EXTENSION};
+use crate::params::param_spec;
+use crate::periodicity::{PeriodTarget, Periodicity};
+
+pub const DEFAULT_SEARCH_OUT: &str = "search";
+pub const DEFAULT_SEARCH_SIZE: (u32, u32) = (64, 64);
+pub const DEFAULT_EVAL_STEPS: u64 = 300;
+const PATCH_SIZE: usize = 20;
+const BATCH_SIZE: usize = 32;
+const SPEED_BINS: usize = 8;
+const MAX_SPEED: f64 = 0.8; // Cells per generation at the top speed bin
+const SIZE_BINS: usize = 8;
+const MIN_SIZE: f64 = 10.0; // Mass at the bottom size bin; bins double from here
+const DIED_MASS: f64 = 1.0;
+const EXPLODED_FRACTION: f64 = 0.5;
+
+// What gets mutated: the kernel and growth parameters plus the starting patch
+#[derive(Clone)]
+struct Genome {
+    kernel_rad: u32,
+    bell_m: f64,
+    bell_s: f64,
+    patch: Vec<f64>, // PATCH_SIZE x PATCH_SIZE
+}
+
+// Bounds `set_parameter` enforces, so genomes never hold values the run cannot use
+fn bounds(name: &str) -> (f64, f64) {
+    param_spec(name).map_or((0.0, 1.0), |p| (p.min, p.max))
+}
+
+// Patterns store cells to four decimals, so genomes do too; a saved find
+// then starts from exactly the state it was scored on
+fn quantise(v: f64) -> f64 {
+    (v.clamp(0.0, 1.0) * 10_000.0).round() / 10_000.0
+}
+
+impl Genome {
+    fn random(rng: &mut StdRng) -> Self {
+        let ((m_min, m_max), (s_min, s_max)) = (bounds("bell_m"), bounds("bell_s"));
+        let r = PATCH_SIZE as f64 / 2.0;
+        let patch = (0..PATCH_SIZE * PATCH_SIZE)
+            .map(|i| {
+                let (x, y) = ((i % PATCH_SIZE) as f64 + 0.5 - r, (i / PATCH_SIZE) as f64 + 0.5 - r);
+                if x.hypot(y) < r { quantise(rng.gen()) } else { 0.0 }
+            })
+            .collect();
+        Self {
+            kernel_rad: rng.gen_range(DEFAULT_KERNEL_RAD - 3..=DEFAULT_KERNEL_RAD + 3),
+            bell_m: rng.gen_range((DEFAULT_BELL_M * 0.5).max(m_min)..(DEFAULT_BELL_M * 2.0).min(m_max)),
+            bell_s: rng.gen_range((DEFAULT_BELL_S * 0.5).max(s_min)..(DEFAULT_BELL_S * 2.0).min(s_max)),
+            patch,
+        }
+    }
+
+    fn mutate(&self, rng: &mut StdRng) -> Self {
+        let mut child = self.clone();
+        if rng.gen_bool(0.2) {
+            child.kernel_rad = if rng.gen() { child.kernel_rad + 1 } else { child.kernel_rad.saturating_sub(1) }.clamp(2, 20);
+        }
+        let ((m_min, m_max), (s_min, s_max)) = (bounds("bell_m"), bounds("bell_s"));
+        child.bell_m = (child.bell_m + rng.gen_range(-0.01..0.01)).clamp(m_min, m_max);
+        child.bell_s = (child.bell_s + rng.gen_range(-0.002..0.002)).clamp(s_min, s_max);
+        for v in &mut child.patch {
+            if rng.gen_bool(0.1) {
+                *v = quantise(*v + rng.gen_range(-0.2..0.2));
+            }
+        }
+        child
+    }
+
+    fn pattern(&self, name: String, comments: Vec<String>) -> Pattern {
+        Pattern {
+            name,
+            comments,
+            params: vec![
+                ("kernel_rad".to_string(), self.kernel_rad as f64),
+                ("bell_m".to_string(), self.bell_m),
+                ("bell_s".to_string(), self.bell_s),
+            ],
+            width: PATCH_SIZE,
+            height: PATCH_SIZE,
+            cells: self.patch.clone(),
+        }
+    }
+}
+
+struct Evaluation {
+    survival: f64,     // Fraction of the run it neither died nor exploded
+    stability: f64,    // 1 for constant mass over the second half, towards 0 for wild swings
+    displacement: f64, // Cells travelled by the heaviest creature
+    mass: f64,         // Mean over the second half
+    repeat: Option<Periodicity>, // Of the heaviest creature at the end of the run
+    score: f64,
+}
+
+impl Evaluation {
+    // Archive cell by speed and (log) size; None for runs that did not last
+    fn niche(&self, steps: u64) -> Option<(usize, usize)> {
+        if self.survival < 1.0 {
+            return None;
+        }
+        let speed = self.displacement / steps as f64;
+        let speed_bin = ((speed / MAX_SPEED * SPEED_BINS as f64) as usize).min(SPEED_BINS - 1);
+        let size_bin = ((self.mass / MIN_SIZE).max(1.0).log2() as usize).min(SIZE_BINS - 1);
+        Some((speed_bin, size_bin))
+    }
+}
+
+fn evaluate(template: &BatchTemplate, genome: &Genome, steps: u64) -> Evaluation {
+    let mut game = template.game();
+    let (a_width, a_height) = (game.a_width, game.a_height);
+    // Gliders need room to travel and a clean start
+    game.wrap_edges = true;
+    game.noise_enabled = false;
+    let pattern = genome.pattern(String::new(), Vec::new());
+    game.pxl_vec.iter_mut().for_each(|v| *v = 0.0);
+    game.apply_pattern_params(&pattern);
+    game.stamp_pattern(&pattern, a_width as i32 / 2, a_height as i32 / 2);
+    // Forget the template's field, which would otherwise be the first rewind frame
+    game.restart_from_field();
+
+    let mut masses = Vec::new();
+    let (mut dx, mut dy) = (0.0, 0.0);
+    let mut survived = 0;
+    for generation in 0..steps {
+        game.step();
+        let (mass, live_fraction) = game.stats.latest().map_or((0.0, 0.0), |o| (o.mass, o.live_fraction));
+        if mass < DIED_MASS || live_fraction > EXPLODED_FRACTION {
+            break;
+        }
+        survived += 1;
+        if generation >= steps / 2 {
+            masses.push(mass);
+        }
+        if let Some(c) = game.tracker.creatures.iter().max_by(|a, b| a.mass.total_cmp(&b.mass)) {
+            dx += c.velocity_x;
+            dy += c.velocity_y;
+        }
+    }
+
+    let mean = masses.iter().sum::<f64>() / masses.len().max(1) as f64;
+    let spread = (masses.iter().map(|m| (m - mean).powi(2)).sum::<f64>() / masses.len().max(1) as f64).sqrt();
+    let survival = survived as f64 / steps as f64;
+    let stability = if mean > 0.0 { 1.0 / (1.0 + 10.0 * spread / mean) } else { 0.0 };
+    let displacement = dx.hypot(dy);
+    let repeat = if survived == steps { game.detect_periodicity(PeriodTarget::Creature) } else { None };
+    Evaluation { survival, stability, displacement, mass: mean, repeat, score: survival * stability * (1.0 + displacement) }
+}
+
+// MAP-Elites: keep the best genome found for every (speed, size) niche and
+// breed new candidates from random elites. Each batch is evaluated in
+// parallel, but the random draws and archive updates happen in order, so a
+// seed always gives the same archive
+pub fn run(options: &Options, iterations: usize) -> Result<(), String> {
+    let steps = options.steps.unwrap_or(DEFAULT_EVAL_STEPS);
+    let out = options.search_out.as_deref().unwrap_or(DEFAULT_SEARCH_OUT);
+    let mut rng = StdRng::seed_from_u64(options.seed.unwrap_or(DEFAULT_SEED));
+    let (a_width, a_height) = options.size.unwrap_or(DEFAULT_SEARCH_SIZE);
+    let template = BatchTemplate::new(options, a_width, a_height)?;
+    let mut archive: Vec<Option<(Genome, Evaluation)>> = (0..SPEED_BINS * SIZE_BINS).map(|_| None).collect();
+
+    for iteration in 0..iterations {
+        let elites: Vec<&Genome> = archive.iter().flatten().map(|(g, _)| g).collect();
+        let candidates: Vec<Genome> = (0..BATCH_SIZE)
+            .map(|_| {
+                if elites.is_empty() || rng.gen_bool(0.1) {
+                    Genome::random(&mut rng)
+                } else {
+                    elites[rng.gen_range(0..elites.len())].mutate(&mut rng)
+                }
+            })
+            .collect();
+        let evaluations: Vec<Evaluation> = candidates.par_iter().map(|g| evaluate(&template, g, steps)).collect();
+
+        for (genome, evaluation) in candidates.into_iter().zip(evaluations) {
+            let Some((speed_bin, size_bin)) = evaluation.niche(steps) else { continue };
+            let slot = &mut archive[size_bin * SPEED_BINS + speed_bin];
+            if slot.as_ref().is_none_or(|(_, e)| evaluation.score > e.score) {
+                *slot = Some((genome, evaluation));
+            }
+        }
+        let filled = archive.iter().flatten().count();
+        let best = archive.iter().flatten().map(|(_, e)| e.score).fold(0.0, f64::max);
+        println!("Iteration {}: {} niches filled, best score {:.3}", iteration + 1, filled, best);
+    }
+
+    fs::create_dir_all(out).map_err(|e| format!("{}: {}", out, e))?;
+    let csv_path = format!("{}/archive.csv", out);
+    let mut csv = BufWriter::new(File::create(&csv_path).map_err(|e| format!("{}: {}", csv_path, e))?);
+    let mut write_csv = |line: String| writeln!(csv, "{}", line).map_err(|e| format!("{}: {}", csv_path, e));
+    write_csv("file,speed_bin,size_bin,score,survival,stability,displacement,mass,period,period_dx,period_dy,period_speed".to_string())?;
+    for (i, (genome, e)) in archive.iter().enumerate().filter_map(|(i, slot)| slot.as_ref().map(|s| (i, s))) {
+        let (speed_bin, size_bin) = (i % SPEED_BINS, i / SPEED_BINS);
+        let name = format!("speed{}_size{}", speed_bin, size_bin);
+        let comments = vec![
+            format!("found by search with seed {}, evaluated with wrapping on and noise off", options.seed.unwrap_or(DEFAULT_SEED)),
+            format!("score {:.4} survival {:.3} stability {:.3} displacement {:.2} mass {:.1}", e.score, e.survival, e.stability, e.displacement, e.mass),
+            e.repeat.map_or("no repeat found".to_string(), |p| p.describe()),
+        ];
+        let repeat = e.repeat.map_or(",,,".to_string(), |p| format!("{},{},{},{}", p.period, p.dx, p.dy, p.speed()));
+        let file = format!("{}.{}", name, PATTERN_EXTENSION);
+        genome.pattern(name, comments).save(&format!("{}/{}", out, file))?;
+        write_csv(format!("{},{},{},{},{},{},{},{},{}", file, speed_bin, size_bin, e.score, e.survival, e.stability, e.displacement, e.mass, repeat))?;
+    }
+    println!("Saved {} patterns to {}", archive.iter().flatten().count(), out);
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::fs;
+use std::fmt::Write;
+use crate::game::GameOfLife;
+use crate::history::Snapshot;
+use crate::integrate::Integrator;
+use crate::midi::MidiBinding;
+use crate::modulation::{Envelope, Lfo, ModRoute};
+use crate::params::PARAMETERS;
+use crate::param_map::{MapKind, ParamMaps};
+use crate::noise::NoiseModel;
+use crate::init::InitGenerator;
+
+pub const SESSION_PATH: &str = "session.lenia";
+const SESSION_HEADER: &str = "lenia-session 1";
+
+// Sessions are plain text, one "key value..." line per setting, followed by
+// the field as one line of cell values per row. The WAV analysis is not
+// stored; pass --wav again when loading a session that uses it
+impl GameOfLife {
+    pub fn session_text(&self) -> String {
+        let mut out = String::new();
+        let p = &self.snapshot().params;
+        // Writing to a String cannot fail
+        let _ = writeln!(out, "{}", SESSION_HEADER);
+        let _ = writeln!(out, "size {} {}", self.a_width, self.a_height);
+        let _ = writeln!(out, "pixel_edge_size {}", p.pixel_edge_size);
+        let _ = writeln!(out, "generation {}", self.generation);
+        let _ = writeln!(out, "integrator {:?}", p.integrator);
+        let _ = writeln!(out, "noise_enabled {}", p.noise_enabled);
+        let _ = writeln!(out, "wrap_edges {}", p.wrap_edges);
+        let _ = writeln!(out, "noise_seed {}", self.noise_seed);
+        let _ = writeln!(out, "noise {}", self.noise.spec());
+        let _ = writeln!(out, "init {}", self.init.spec());
+        for spec in PARAMETERS.iter() {
+            if let Some(value) = self.get_parameter(spec.name) {
+                let _ = writeln!(out, "param {} {}", spec.name, value);
+            }
+        }
+        let _ = writeln!(out, "clock {} {}", self.clock.bpm, self.clock.steps_per_sixteenth);
+        let _ = writeln!(out, "mod_time {}", self.modulation.time);
+        for lfo in &self.modulation.lfos {
+            let _ = writeln!(out, "lfo {} {} {}", lfo.spec(), lfo.phase, lfo.held);
+        }
+        for envelope in &self.modulation.envelopes {
+            let _ = writeln!(out, "envelope {} {}", envelope.spec(), envelope.state());
+        }
+        for route in &self.modulation.routes {
+            let _ = writeln!(out, "route {}", route.spec());
+        }
+        for binding in &self.midi_bindings {
+            let _ = writeln!(out, "midi {}", binding.spec());
+        }
+        for kind in MapKind::ALL {
+            if let Some(map) = self.param_maps.get(kind, self.pxl_vec.len()) {
+                let values: Vec<String> = map.iter().map(|v| format!("{:.4}", v)).collect();
+                let _ = writeln!(out, "map {} {}", kind.name(), values.join(" "));
+            }
+        }
+        if let Some(mask) = self.mask_text() {
+            let _ = writeln!(out, "mask {}", mask);
+        }
+        let _ = writeln!(out, "field");
+        for row in self.pxl_vec.chunks(self.a_width as usize) {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            let _ = writeln!(out, "{}", values.join(" "));
+        }
+        out
+    }
+
+    pub fn load_session_text(&mut self, text: &str) -> Result<(), String> {
+        let mut lines = text.lines();
+        if lines.next().map(str::trim) != Some(SESSION_HEADER) {
+            return Err("not a session file".to_string());
+        }
+
+        let current = self.snapshot();
+        let mut params = current.params.clone();
+        let (mut a_width, mut a_height) = (current.a_width, current.a_height);
+        let mut generation = self.generation;
+        let mut noise_seed = self.noise_seed;
+        let mut noise = self.noise.clone();
+        let mut init = self.init.clone();
+        let mut values = Vec::new();
+        let (mut bpm, mut steps_per_sixteenth) = (self.clock.bpm, self.clock.steps_per_sixteenth);
+        let mut mod_time = 0.0;
+        let (mut lfos, mut envelopes, mut routes) = (Vec::new(), Vec::new(), Vec::new());
+        let mut midi_bindings = Vec::new();
+        let mut maps = Vec::new();
+        let mut mask = None;
+        let mut field = None;
+
+        let bad = |line: &str| format!("bad session line '{}'", line);
+        for line in lines.by_ref() {
+            let (key, rest) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            let number = |t: &str| t.parse::<f64>().map_err(|_| bad(line));
+            match key {
+                "" => {}
+                "size" => {
+                    let (w, h) = rest.split_once(' ').ok_or(bad(line))?;
+                    a_width = w.parse().map_err(|_| bad(line))?;
+                    a_height = h.parse().map_err(|_| bad(line))?;
+                }
+                "pixel_edge_size" => params.pixel_edge_size = rest.parse().map_err(|_| bad(line))?,
+                "generation" => generation = rest.parse().map_err(|_| bad(line))?,
+                "integrator" => params.integrator = Integrator::from_name(rest).ok_or(bad(line))?,
+                "noise_enabled" => params.noise_enabled = rest.parse().map_err(|_| bad(line))?,
+                "wrap_edges" => params.wrap_edges = rest.parse().map_err(|_| bad(line))?,
+                "noise_seed" => noise_seed = rest.parse().map_err(|_| bad(line))?,
+                "noise" => noise = NoiseModel::parse(rest)?,
+                "init" => init = InitGenerator::parse(rest)?,
+                "param" => {
+                    let (name, value) = rest.split_once(' ').ok_or(bad(line))?;
+                    values.push((name.to_string(), number(value)?));
+                }
+                "clock" => {
+                    let (b, k) = rest.split_once(' ').ok_or(bad(line))?;
+                    bpm = number(b)?;
+                    steps_per_sixteenth = k.parse().map_err(|_| bad(line))?;
+                }
+                "mod_time" => mod_time = number(rest)?,
+                "lfo" => {
+                    let parts: Vec<&str> = rest.split_whitespace().collect();
+                    let [spec, phase, held] = parts[..] else { return Err(bad(line)) };
+                    let mut lfo = Lfo::parse(spec)?;
+                    lfo.phase = number(phase)?;
+                    lfo.held = number(held)?;
+                    lfos.push(lfo);
+                }
+                "envelope" => {
+                    let (spec, state) = rest.split_once(' ').ok_or(bad(line))?;
+                    let mut envelope = Envelope::parse(spec)?;
+                    envelope.restore_state(state)?;
+                    envelopes.push(envelope);
+                }
+                "route" => routes.push(ModRoute::parse(rest)?),
+                "midi" => midi_bindings.push(MidiBinding::parse(rest)?),
+                "map" => {
+                    let (kind, values) = rest.split_once(' ').ok_or(bad(line))?;
+                    let kind = MapKind::from_name(kind).ok_or(bad(line))?;
+                    let values: Result<Vec<f64>, String> = values.split_whitespace().map(number).collect();
+                    maps.push((kind, values?));
+                }
+                "mask" => mask = Some(GameOfLife::parse_mask(rest)?),
+                "field" => {
+                    let cells: Result<Vec<f64>, String> =
+                        lines.by_ref().flat_map(str::split_whitespace).map(number).collect();
+                    field = Some(cells?);
+                    break;
+                }
+                _ => return Err(bad(line)),
+            }
+        }
+
+        let field = field.ok_or("session has no field")?;
+        if field.len() != (a_width * a_height) as usize {
+            return Err(format!("field has {} cells, expected {}x{}", field.len(), a_width, a_height));
+        }
+        if let Some((kind, _)) = maps.iter().find(|(_, map)| map.len() != field.len()) {
+            return Err(format!("{} map does not match the field size", kind.name()));
+        }
+        if mask.as_ref().is_some_and(|m| m.len() != field.len()) {
+            return Err("mask does not match the field size".to_string());
+        }
+
+        self.record_history();
+        self.generation = generation;
+        self.noise_seed = noise_seed;
+        self.noise = noise;
+        self.init = init;
+        let mut param_maps = ParamMaps::default();
+        for (kind, map) in maps {
+            param_maps.set(kind, map);
+        }
+        self.restore(Snapshot { field, a_width, a_height, params, param_maps, mask });
+        for (name, value) in values {
+            self.set_parameter(&name, value);
+        }
+        self.clock.set_bpm(bpm);
+        self.clock.steps_per_sixteenth = steps_per_sixteenth.max(1);
+        self.sync_clock();
+        self.modulation.time = mod_time;
+        self.modulation.lfos = lfos;
+        self.modulation.envelopes = envelopes;
+        self.modulation.routes = routes;
+        self.midi_bindings = midi_bindings;
+        self.apply_mask();
+        self.rewind.clear();
+        self.rewind.push(self.generation, &self.pxl_vec);
+        self.tracker.clear();
+        self.pending_notes.clear();
+        self.periodicity = None;
+        if let Ok(mut synth) = self.synth.lock() {
+            synth.all_notes_off();
+        }
+        Ok(())
+    }
+
+    pub fn save_session(&self, path: &str) {
+        match fs::write(path, self.session_text()) {
+            Ok(()) => println!("Saved session to {}", path),
+            Err(e) => eprintln!("Failed to save session: {}", e),
+        }
+    }
+
+    pub fn load_session(&mut self, path: &str) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        self.load_session_text(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
@@ -0,0 +1,56 @@
+use crate::game::GameOfLife;
+use crate::synth::VoiceParams;
+use crate::tracking::{Creature, CreatureEvent};
+
+pub const MASS_HALF_LOUDNESS: f64 = 200.0; // Mass at which a voice reaches half amplitude
+
+impl GameOfLife {
+    // Higher on screen is higher in pitch, left/right is pan, heavier is louder
+    // and ragged shapes sound brighter than round ones
+    pub fn creature_voice(&self, creature: &Creature) -> VoiceParams {
+        VoiceParams {
+            freq: self.quantiser.freq(1.0 - creature.centroid_y / self.a_height as f64),
+            pan: (creature.centroid_x / self.a_width as f64).clamp(0.0, 1.0),
+            amp: creature.mass / (creature.mass + MASS_HALF_LOUDNESS),
+            brightness: 1.0 - creature.compactness(),
+        }
+    }
+
+    // Forward the tracker's births and deaths to the synth as note-ons and
+    // note-offs. Note-ons wait for the next 16th of the tempo clock
+    pub fn sonify_creatures(&mut self) {
+        let Ok(mut synth) = self.synth.lock() else {
+            return;
+        };
+
+        for event in &self.tracker.events {
+            match *event {
+                CreatureEvent::Birth { id } | CreatureEvent::Split { child: id, .. } => {
+                    self.pending_notes.push(id);
+                }
+                CreatureEvent::Death { id } | CreatureEvent::Merge { absorbed: id, .. } => {
+                    self.pending_notes.retain(|&p| p != id);
+                    synth.note_off(id);
+                }
+            }
+        }
+
+        if self.clock.on_sixteenth(self.generation) {
+            for id in std::mem::take(&mut self.pending_notes) {
+                if let Some(creature) = self.tracker.creatures.iter().find(|c| c.id == id) {
+                    synth.note_on(id, self.creature_voice(creature));
+                }
+            }
+        }
+
+        for creature in &self.tracker.creatures {
+            synth.set(creature.id, self.creature_voice(creature));
+        }
+    }
+
+    pub fn toggle_sound(&mut self) {
+        if let Ok(mut synth) = self.synth.lock() {
+            synth.enabled = !synth.enabled;
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+use std::f64::consts::TAU;
+use fft2d::slice::ifft_2d;
+use num_complex::Complex;
+use crate::game::GameOfLife;
+use crate::synth::{SynthMode, SAMPLE_RATE};
+
+pub const SPECTRAL_FRAME: usize = 2048;
+pub const SPECTRAL_HOP: usize = SPECTRAL_FRAME / 4;
+const MAGNITUDE_GLIDE: f64 = 0.3; // Per frame, smooths the once-per-step jumps
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SliceMode {
+    Row,
+    Column,
+    Radial,
+}
+
+impl SliceMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "row" => Some(SliceMode::Row),
+            "column" => Some(SliceMode::Column),
+            "radial" => Some(SliceMode::Radial),
+            _ => None,
+        }
+    }
+}
+
+// Inverse-FFT resynthesis of a magnitude spectrum. Frames are Hann windowed
+// and overlap-added at a quarter frame; each bin's phase advances by its
+// centre frequency between frames so steady partials stay continuous
+pub struct SpectralSynth {
+    target: Vec<f64>, // Magnitude per bin, 0..SPECTRAL_FRAME / 2
+    current: Vec<f64>,
+    phases: Vec<f64>,
+    window: Vec<f64>,
+    overlap: Vec<f64>, // Tail of the previous frames still to be added
+    output: VecDeque<f32>,
+}
+
+impl SpectralSynth {
+    pub fn new() -> Self {
+        let bins = SPECTRAL_FRAME / 2;
+        Self {
+            target: vec![0.0; bins],
+            current: vec![0.0; bins],
+            phases: vec![0.0; bins],
+            window: (0..SPECTRAL_FRAME).map(|i| 0.5 - 0.5 * (TAU * i as f64 / SPECTRAL_FRAME as f64).cos()).collect(),
+            overlap: vec![0.0; SPECTRAL_FRAME],
+            output: VecDeque::new(),
+        }
+    }
+
+    // Slice values become the magnitudes at the given frequencies; several
+    // values landing in one bin keep the loudest
+    pub fn set_spectrum(&mut self, freqs: &[f64], magnitudes: &[f64]) {
+        self.target.iter_mut().for_each(|m| *m = 0.0);
+        let bin_hz = SAMPLE_RATE as f64 / SPECTRAL_FRAME as f64;
+        for (&freq, &magnitude) in freqs.iter().zip(magnitudes) {
+            let bin = (freq / bin_hz).round() as usize;
+            if bin > 0 && bin < self.target.len() {
+                self.target[bin] = self.target[bin].max(magnitude);
+            }
+        }
+    }
+
+    fn synthesise_frame(&mut self) {
+        let n = SPECTRAL_FRAME;
+        let mut spectrum = vec![Complex::new(0.0, 0.0); n];
+        for k in 1..n / 2 {
+            self.current[k] += (self.target[k] - self.current[k]) * MAGNITUDE_GLIDE;
+            self.phases[k] = (self.phases[k] + TAU * k as f64 * SPECTRAL_HOP as f64 / n as f64) % TAU;
+            let bin = Complex::from_polar(self.current[k], self.phases[k]);
+            spectrum[k] = bin;
+            spectrum[n - k] = bin.conj();
+        }
+        // A single row, so the 2D transform is a plain 1D inverse FFT
+        ifft_2d(n, 1, &mut spectrum);
+
+        // The unnormalised IFFT turns a conjugate pair of magnitude m into a
+        // cosine of amplitude 2m, and Hann windows at 75% overlap sum to 2.
+        // Dividing by the total magnitude as well keeps the sum from clipping
+        let total: f64 = self.current.iter().sum();
+        let scale = 1.0 / (4.0 * total.max(1.0));
+        for ((acc, bin), w) in self.overlap.iter_mut().zip(&spectrum).zip(&self.window) {
+            *acc += bin.re * w * scale;
+        }
+        self.output.extend(self.overlap[..SPECTRAL_HOP].iter().map(|&s| s as f32));
+        self.overlap.rotate_left(SPECTRAL_HOP);
+        self.overlap[n - SPECTRAL_HOP..].iter_mut().for_each(|s| *s = 0.0);
+    }
+
+    // Mono into both channels of an interleaved stereo buffer
+    pub fn render(&mut self, out: &mut [f32], gain: f64) {
+        for frame in out.chunks_exact_mut(2) {
+            if self.output.is_empty() {
+                self.synthesise_frame();
+            }
+            let sample = self.output.pop_front().unwrap_or(0.0) * gain as f32;
+            frame[0] += sample;
+            frame[1] += sample;
+        }
+    }
+}
+
+impl GameOfLife {
+    // Start and end of the slice in cell coordinates. Row and column slices
+    // sweep the field once per bar of the tempo clock (or sit on the followed
+    // creature); radial slices turn around the field centre or the creature
+    pub fn spectral_line(&self, mode: SliceMode) -> ((f64, f64), (f64, f64)) {
+        let (w, h) = (self.a_width as f64, self.a_height as f64);
+        let steps_per_bar = self.clock.steps_per_beat() * 4.0;
+        let sweep = (self.generation as f64 % steps_per_bar) / steps_per_bar;
+        let followed = if self.follow_creature {
+            self.tracker.creatures.iter().max_by(|a, b| a.mass.total_cmp(&b.mass)).map(|c| (c.centroid_x, c.centroid_y))
+        } else {
+            None
+        };
+        match mode {
+            SliceMode::Row => {
+                let y = followed.map_or(sweep * h, |c| c.1);
+                ((0.0, y), (w, y))
+            }
+            // Bottom to top, so higher on screen is higher in pitch as for voices
+            SliceMode::Column => {
+                let x = followed.map_or(sweep * w, |c| c.0);
+                ((x, h), (x, 0.0))
+            }
+            SliceMode::Radial => {
+                let (cx, cy) = followed.unwrap_or((w / 2.0, h / 2.0));
+                let r = w.min(h) / 2.0;
+                let angle = sweep * TAU;
+                ((cx, cy), (cx + r * angle.cos(), cy + r * angle.sin()))
+            }
+        }
+    }
+
+    // Cell values along the slice, one sample per cell of length. Samples sit
+    // at cell centres, so the far end of a row or column is never wrapped
+    // back onto its first cell
+    pub fn spectral_slice(&self, mode: SliceMode) -> Vec<f64> {
+        let ((x0, y0), (x1, y1)) = self.spectral_line(mode);
+        let samples = (x1 - x0).hypot(y1 - y0).round().max(2.0) as usize;
+        let (w, h) = (self.a_width as i64, self.a_height as i64);
+        (0..samples)
+            .map(|i| {
+                let t = (i as f64 + 0.5) / samples as f64;
+                let x = (x0 + (x1 - x0) * t).floor() as i64;
+                let y = (y0 + (y1 - y0) * t).floor() as i64;
+                // Out-of-field samples wrap, matching the kernel on a torus
+                self.pxl_vec[(y.rem_euclid(h) * w + x.rem_euclid(w)) as usize]
+            })
+            .collect()
+    }
+
+    // Hand the current slice to the synth; positions along it map through
+    // the shared quantiser, so the spectrum is tuned to the active scale
+    pub fn sonify_spectrum(&mut self) {
+        let Some(mode) = self.spectral else {
+            return;
+        };
+        let slice = self.spectral_slice(mode);
+        let last = (slice.len() - 1).max(1) as f64;
+        let freqs: Vec<f64> = (0..slice.len()).map(|i| self.quantiser.freq(i as f64 / last)).collect();
+        if let Ok(mut synth) = self.synth.lock() {
+            synth.spectral.set_spectrum(&freqs, &slice);
+        }
+    }
+
+    // X: voices -> row -> column -> radial -> voices
+    pub fn cycle_spectral(&mut self) {
+        self.spectral = match self.spectral {
+            None => Some(SliceMode::Row),
+            Some(SliceMode::Row) => Some(SliceMode::Column),
+            Some(SliceMode::Column) => Some(SliceMode::Radial),
+            Some(SliceMode::Radial) => None,
+        };
+        self.sync_synth_mode();
+    }
+
+    pub fn sync_synth_mode(&mut self) {
+        let mode = if self.spectral.is_some() { SynthMode::Spectral } else { SynthMode::Voices };
+        if let Ok(mut synth) = self.synth.lock() {
+            synth.mode = mode;
+        }
+        self.sonify_spectrum();
+    }
+}
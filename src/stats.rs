@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Write};
+
+pub const LIVE_THRESHOLD: f64 = 0.1;
+pub const HISTOGRAM_BINS: usize = 32;
+pub const MAX_LOGGED_ROWS: usize = 100_000;
+pub const SPARKLINE_LEN: usize = 200;
+
+#[derive(Clone, Copy, Default)]
+pub struct Observables {
+    pub generation: u64,
+    pub mass: f64,
+    pub centroid_x: f64,
+    pub centroid_y: f64,
+    pub velocity_x: f64,
+    pub velocity_y: f64,
+    pub growth_mean: f64,
+    pub variance: f64, // Mass-weighted spread of positions around the centroid
+    pub entropy: f64,  // Shannon entropy of the value histogram, in bits
+    pub live_fraction: f64,
+}
+
+impl Observables {
+    pub fn compute(field: &[f64], a_width: u32, generation: u64, growth_mean: f64, prev: Option<&Observables>) -> Self {
+        let mut mass = 0.0;
+        let mut sum_x = 0.0;
+        let mut sum_y = 0.0;
+        let mut live = 0;
+        let mut histogram = [0usize; HISTOGRAM_BINS];
+
+        for (i, &val) in field.iter().enumerate() {
+            let x = (i % a_width as usize) as f64;
+            let y = (i / a_width as usize) as f64;
+            mass += val;
+            sum_x += val * x;
+            sum_y += val * y;
+            if val > LIVE_THRESHOLD {
+                live += 1;
+            }
+            histogram[((val * HISTOGRAM_BINS as f64) as usize).min(HISTOGRAM_BINS - 1)] += 1;
+        }
+
+        let (centroid_x, centroid_y) = if mass > 0.0 { (sum_x / mass, sum_y / mass) } else { (0.0, 0.0) };
+
+        let mut variance = 0.0;
+        if mass > 0.0 {
+            for (i, &val) in field.iter().enumerate() {
+                let dx = (i % a_width as usize) as f64 - centroid_x;
+                let dy = (i / a_width as usize) as f64 - centroid_y;
+                variance += val * (dx * dx + dy * dy);
+            }
+            variance /= mass;
+        }
+
+        let total = field.len().max(1) as f64;
+        let entropy = histogram
+            .iter()
+            .filter(|&&n| n > 0)
+            .map(|&n| {
+                let p = n as f64 / total;
+                -p * p.log2()
+            })
+            .sum();
+
+        let (velocity_x, velocity_y) = match prev {
+            Some(p) if generation > p.generation => {
+                let elapsed = (generation - p.generation) as f64;
+                ((centroid_x - p.centroid_x) / elapsed, (centroid_y - p.centroid_y) / elapsed)
+            }
+            _ => (0.0, 0.0),
+        };
+
+        Self {
+            generation,
+            mass,
+            centroid_x,
+            centroid_y,
+            velocity_x,
+            velocity_y,
+            growth_mean,
+            variance,
+            entropy,
+            live_fraction: live as f64 / total,
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.velocity_x.hypot(self.velocity_y)
+    }
+}
+
+pub struct StatsLog {
+    pub rows: VecDeque<Observables>,
+}
+
+impl StatsLog {
+    pub fn new() -> Self {
+        Self { rows: VecDeque::new() }
+    }
+
+    pub fn push(&mut self, row: Observables) {
+        self.rows.push_back(row);
+        while self.rows.len() > MAX_LOGGED_ROWS {
+            self.rows.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&Observables> {
+        self.rows.back()
+    }
+
+    // The most recent values of one observable, oldest first
+    pub fn series(&self, value: fn(&Observables) -> f64) -> Vec<f64> {
+        let skip = self.rows.len().saturating_sub(SPARKLINE_LEN);
+        self.rows.iter().skip(skip).map(value).collect()
+    }
+
+    pub fn export_csv(&self, path: &str) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "generation,mass,centroid_x,centroid_y,velocity_x,velocity_y,growth_mean,variance,entropy,live_fraction")?;
+        for r in &self.rows {
+            writeln!(
+                file,
+                "{},{},{},{},{},{},{},{},{},{}",
+                r.generation, r.mass, r.centroid_x, r.centroid_y, r.velocity_x, r.velocity_y,
+                r.growth_mean, r.variance, r.entropy, r.live_fraction
+            )?;
+        }
+        Ok(())
+    }
+}
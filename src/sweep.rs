@@ -0,0 +1,175 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use rayon::prelude::*;
+use crate::batch::BatchTemplate;
+use crate::cli::{Options, DEFAULT_HEADLESS_SIZE};
+use crate::image_io::write_png;
+use crate::params::param_spec;
+use crate::periodicity::{PeriodTarget, Periodicity};
+
+pub const DEFAULT_SWEEP_STEPS: u64 = 400;
+pub const DEFAULT_SWEEP_OUT: &str = "sweep";
+const WINDOW: usize = 100; // Final generations the classification looks at
+const PIXELS_PER_RUN: usize = 12;
+const DIED_MASS: f64 = 1.0;
+const EXPLODED_FRACTION: f64 = 0.5;
+const MOVING_SPEED: f64 = 0.05; // Cells per generation
+const STABLE_VARIATION: f64 = 1e-3; // Relative spread of the mass
+
+// One axis of the grid: "bell_m:0.05:0.5:20" runs 20 values from 0.05 to 0.5
+#[derive(Clone, Debug)]
+pub struct SweepAxis {
+    pub param: String,
+    pub min: f64,
+    pub max: f64,
+    pub steps: usize,
+}
+
+impl SweepAxis {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let [param, min, max, steps] = parts[..] else {
+            return Err(format!("sweep axis '{}' is not param:min:max:steps", spec));
+        };
+        if param_spec(param).is_none() {
+            return Err(format!("unknown parameter '{}'", param));
+        }
+        let number = |t: &str| t.parse::<f64>().map_err(|_| format!("bad number '{}'", t));
+        let steps: usize = steps.parse().map_err(|_| format!("bad step count '{}'", steps))?;
+        Ok(Self { param: param.to_string(), min: number(min)?, max: number(max)?, steps: steps.max(1) })
+    }
+
+    pub fn value(&self, i: usize) -> f64 {
+        if self.steps == 1 {
+            self.min
+        } else {
+            self.min + (self.max - self.min) * i as f64 / (self.steps - 1) as f64
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    Died,
+    Exploded,
+    Stable,
+    Oscillating,
+    Moving,
+}
+
+impl Outcome {
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::Died => "died",
+            Outcome::Exploded => "exploded",
+            Outcome::Stable => "stable",
+            Outcome::Oscillating => "oscillating",
+            Outcome::Moving => "moving",
+        }
+    }
+
+    pub fn color(self) -> [u8; 3] {
+        match self {
+            Outcome::Died => [20, 20, 20],
+            Outcome::Exploded => [220, 60, 40],
+            Outcome::Stable => [60, 110, 220],
+            Outcome::Oscillating => [70, 190, 90],
+            Outcome::Moving => [240, 210, 60],
+        }
+    }
+}
+
+pub struct RunResult {
+    pub outcome: Outcome,
+    pub mass: f64,
+    pub speed: f64,     // Mean creature speed over the window
+    pub variation: f64, // Relative standard deviation of the mass over the window
+    pub repeat: Option<Periodicity>, // Shortest repeat of the final field up to translation
+}
+
+// Judge a run from its last WINDOW generations
+pub fn classify(masses: &[f64], live_fraction: f64, speed: f64) -> RunResult {
+    let mass = masses.last().copied().unwrap_or(0.0);
+    let mean = masses.iter().sum::<f64>() / masses.len().max(1) as f64;
+    let spread = (masses.iter().map(|m| (m - mean).powi(2)).sum::<f64>() / masses.len().max(1) as f64).sqrt();
+    let variation = if mean > 0.0 { spread / mean } else { 0.0 };
+    let outcome = if mass < DIED_MASS {
+        Outcome::Died
+    } else if live_fraction > EXPLODED_FRACTION {
+        Outcome::Exploded
+    } else if speed > MOVING_SPEED {
+        Outcome::Moving
+    } else if variation < STABLE_VARIATION {
+        Outcome::Stable
+    } else {
+        Outcome::Oscillating
+    };
+    RunResult { outcome, mass, speed, variation, repeat: None }
+}
+
+fn run_one(template: &BatchTemplate, x: (&str, f64), y: (&str, f64), steps: u64) -> RunResult {
+    let mut game = template.game();
+    game.set_parameter(x.0, x.1);
+    game.set_parameter(y.0, y.1);
+
+    let mut masses = Vec::with_capacity(WINDOW);
+    let mut speeds = Vec::with_capacity(WINDOW);
+    for generation in 0..steps {
+        game.step();
+        if generation + WINDOW as u64 >= steps {
+            masses.push(game.stats.latest().map_or(0.0, |o| o.mass));
+            let creatures = &game.tracker.creatures;
+            let speed = creatures.iter().map(|c| c.velocity_x.hypot(c.velocity_y)).sum::<f64>() / creatures.len().max(1) as f64;
+            speeds.push(speed);
+        }
+    }
+    let live_fraction = game.stats.latest().map_or(0.0, |o| o.live_fraction);
+    let speed = speeds.iter().sum::<f64>() / speeds.len().max(1) as f64;
+    let mut result = classify(&masses, live_fraction, speed);
+    result.repeat = game.detect_periodicity(PeriodTarget::Field);
+    result
+}
+
+// Run every (x, y) pair of the grid in parallel from the same start, then
+// write <out>.csv and a <out>.png phase diagram with x to the right and y up
+pub fn run(options: &Options, x_axis: &SweepAxis, y_axis: &SweepAxis) -> Result<(), String> {
+    let steps = options.steps.unwrap_or(DEFAULT_SWEEP_STEPS);
+    let out = options.sweep_out.as_deref().unwrap_or(DEFAULT_SWEEP_OUT);
+
+    let cells: Vec<(usize, usize)> = (0..y_axis.steps).flat_map(|j| (0..x_axis.steps).map(move |i| (i, j))).collect();
+    let (a_width, a_height) = options.size.unwrap_or(DEFAULT_HEADLESS_SIZE);
+    let template = BatchTemplate::new(options, a_width, a_height)?;
+    let results: Vec<RunResult> = cells
+        .par_iter()
+        .map(|&(i, j)| run_one(&template, (&x_axis.param, x_axis.value(i)), (&y_axis.param, y_axis.value(j)), steps))
+        .collect();
+
+    let csv_path = format!("{}.csv", out);
+    let write_csv = || -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(&csv_path)?);
+        writeln!(file, "{},{},outcome,mass,speed,mass_variation,period,period_dx,period_dy,period_speed", x_axis.param, y_axis.param)?;
+        for (&(i, j), r) in cells.iter().zip(&results) {
+            // The period columns stay empty when nothing repeated
+            let repeat = r.repeat.map_or(",,,".to_string(), |p| format!("{},{},{},{}", p.period, p.dx, p.dy, p.speed()));
+            writeln!(file, "{},{},{},{},{},{},{}", x_axis.value(i), y_axis.value(j), r.outcome.name(), r.mass, r.speed, r.variation, repeat)?;
+        }
+        file.flush()
+    };
+    write_csv().map_err(|e| format!("{}: {}", csv_path, e))?;
+
+    let (width, height) = (x_axis.steps * PIXELS_PER_RUN, y_axis.steps * PIXELS_PER_RUN);
+    let mut rgb = vec![0u8; width * height * 3];
+    for (&(i, j), r) in cells.iter().zip(&results) {
+        let row0 = (y_axis.steps - 1 - j) * PIXELS_PER_RUN;
+        for py in row0..row0 + PIXELS_PER_RUN {
+            for px in i * PIXELS_PER_RUN..(i + 1) * PIXELS_PER_RUN {
+                rgb[(py * width + px) * 3..][..3].copy_from_slice(&r.outcome.color());
+            }
+        }
+    }
+    let png_path = format!("{}.png", out);
+    write_png(&png_path, width as u32, height as u32, &rgb).map_err(|e| format!("{}: {}", png_path, e))?;
+
+    println!("Swept {} runs of {} generations into {} and {}", results.len(), steps, csv_path, png_path);
+    Ok(())
+}
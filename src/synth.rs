@@ -0,0 +1,173 @@
+use std::f64::consts::TAU;
+use sdl2::audio::AudioCallback;
+use std::sync::{Arc, Mutex};
+use crate::granular::GrainEngine;
+use crate::spectral::SpectralSynth;
+
+pub const SAMPLE_RATE: i32 = 44100;
+pub const MAX_VOICES: usize = 8;
+const ATTACK_SECONDS: f64 = 0.05;
+const RELEASE_SECONDS: f64 = 0.4;
+const GLIDE_SECONDS: f64 = 0.05; // Smoothing of per-step parameter jumps
+const MAX_HARMONICS: usize = 6;
+
+#[derive(Clone, Copy, Default)]
+pub struct VoiceParams {
+    pub freq: f64,
+    pub pan: f64,        // 0 left, 1 right
+    pub amp: f64,        // 0..1
+    pub brightness: f64, // 0 pure sine, 1 full harmonic stack
+}
+
+struct Voice {
+    id: u64,
+    target: VoiceParams,
+    current: VoiceParams,
+    phase: f64,
+    env: f64,
+    gate: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SynthMode {
+    Voices,
+    Spectral,
+}
+
+// Polyphonic additive synth; each voice follows one sound source (e.g. a creature).
+// In spectral mode a field slice is resynthesised instead of the voices.
+// Grains, when a sample is loaded, play on top of either
+pub struct Synth {
+    voices: Vec<Voice>,
+    pub gain: f64,
+    pub enabled: bool,
+    pub mode: SynthMode,
+    pub spectral: SpectralSynth,
+    pub granular: GrainEngine,
+}
+
+impl Synth {
+    pub fn new() -> Self {
+        Self {
+            voices: Vec::new(),
+            gain: 0.8,
+            enabled: true,
+            mode: SynthMode::Voices,
+            spectral: SpectralSynth::new(),
+            granular: GrainEngine::new(),
+        }
+    }
+
+    pub fn shared() -> Arc<Mutex<Synth>> {
+        Arc::new(Mutex::new(Synth::new()))
+    }
+
+    pub fn note_on(&mut self, id: u64, params: VoiceParams) {
+        let voice = Voice {
+            id,
+            target: params,
+            current: VoiceParams { amp: 0.0, ..params },
+            phase: 0.0,
+            env: 0.0,
+            gate: true,
+        };
+
+        // Reuse a finished voice first, then steal the quietest if the new one is louder
+        self.voices.retain(|v| v.gate || v.env > 1e-4);
+        if self.voices.len() < MAX_VOICES {
+            self.voices.push(voice);
+        } else if let Some(quietest) = self
+            .voices
+            .iter_mut()
+            .min_by(|a, b| (a.env * a.current.amp).total_cmp(&(b.env * b.current.amp)))
+        {
+            if quietest.env * quietest.current.amp < params.amp {
+                *quietest = voice;
+            }
+        }
+    }
+
+    pub fn note_off(&mut self, id: u64) {
+        for voice in self.voices.iter_mut().filter(|v| v.id == id) {
+            voice.gate = false;
+        }
+    }
+
+    pub fn set(&mut self, id: u64, params: VoiceParams) {
+        for voice in self.voices.iter_mut().filter(|v| v.id == id && v.gate) {
+            voice.target = params;
+        }
+    }
+
+    pub fn all_notes_off(&mut self) {
+        for voice in &mut self.voices {
+            voice.gate = false;
+        }
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.iter().filter(|v| v.gate).count()
+    }
+
+    // Fill an interleaved stereo buffer
+    pub fn render(&mut self, out: &mut [f32]) {
+        out.iter_mut().for_each(|s| *s = 0.0);
+        if !self.enabled {
+            return;
+        }
+        match self.mode {
+            SynthMode::Voices => self.render_voices(out),
+            SynthMode::Spectral => self.spectral.render(out, self.gain),
+        }
+        self.granular.render(out);
+    }
+
+    fn render_voices(&mut self, out: &mut [f32]) {
+        let dt = 1.0 / SAMPLE_RATE as f64;
+        let attack = dt / ATTACK_SECONDS;
+        let release = dt / RELEASE_SECONDS;
+        let glide = dt / GLIDE_SECONDS;
+        let gain = self.gain / MAX_VOICES as f64;
+
+        for voice in &mut self.voices {
+            for frame in out.chunks_exact_mut(2) {
+                voice.env = if voice.gate { (voice.env + attack).min(1.0) } else { (voice.env - release).max(0.0) };
+                let c = &mut voice.current;
+                c.freq += (voice.target.freq - c.freq) * glide;
+                c.pan += (voice.target.pan - c.pan) * glide;
+                c.amp += (voice.target.amp - c.amp) * glide;
+                c.brightness += (voice.target.brightness - c.brightness) * glide;
+
+                voice.phase = (voice.phase + c.freq * dt).fract();
+                let mut sample = 0.0;
+                let mut norm = 0.0;
+                for h in 1..=MAX_HARMONICS {
+                    let weight = if h == 1 { 1.0 } else { c.brightness / h as f64 };
+                    sample += weight * (TAU * voice.phase * h as f64).sin();
+                    norm += weight;
+                }
+                let sample = sample / norm * c.amp * voice.env * gain;
+
+                // Equal-power panning
+                frame[0] += (sample * (c.pan * TAU / 4.0).cos()) as f32;
+                frame[1] += (sample * (c.pan * TAU / 4.0).sin()) as f32;
+            }
+        }
+        self.voices.retain(|v| v.gate || v.env > 0.0);
+    }
+}
+
+pub struct SynthCallback {
+    pub synth: Arc<Mutex<Synth>>,
+}
+
+impl AudioCallback for SynthCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        match self.synth.lock() {
+            Ok(mut synth) => synth.render(out),
+            Err(_) => out.iter_mut().for_each(|s| *s = 0.0),
+        }
+    }
+}
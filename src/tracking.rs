@@ -0,0 +1,290 @@
+use std::collections::{BTreeMap, HashMap};
+
+pub const DEFAULT_TRACK_THRESHOLD: f64 = 0.2;
+pub const MIN_CREATURE_CELLS: usize = 4; // Smaller specks are treated as noise
+
+#[derive(Clone, Debug)]
+pub struct Creature {
+    pub id: u64,
+    pub born: u64,
+    pub cells: usize,
+    pub mass: f64,
+    pub centroid_x: f64,
+    pub centroid_y: f64,
+    pub velocity_x: f64,
+    pub velocity_y: f64,
+    // Bounding box in cells; with wrapping it may extend past the field edges
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+    pub perimeter: usize, // Cell edges facing the background
+}
+
+impl Creature {
+    // 1 for a disc, smaller for elongated or ragged shapes
+    pub fn compactness(&self) -> f64 {
+        if self.perimeter == 0 {
+            return 0.0;
+        }
+        let pi = std::f64::consts::PI;
+        let ratio = 4.0 * pi * self.cells as f64 / (self.perimeter * self.perimeter) as f64;
+        // On a grid a disc's perimeter is 8r rather than 2 pi r, so it only scores pi^2 / 16
+        (ratio * 16.0 / (pi * pi)).min(1.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CreatureEvent {
+    Birth { id: u64 },
+    Death { id: u64 },
+    Split { parent: u64, child: u64 },
+    Merge { into: u64, absorbed: u64 },
+}
+
+pub struct Tracker {
+    pub threshold: f64,
+    pub creatures: Vec<Creature>,
+    pub events: Vec<CreatureEvent>, // Events of the latest update only
+    labels: Vec<usize>,             // 0 for background, else index into creatures + 1
+    next_id: u64,
+    last_generation: u64,
+}
+
+impl Tracker {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            creatures: Vec::new(),
+            events: Vec::new(),
+            labels: Vec::new(),
+            next_id: 1,
+            last_generation: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.creatures.clear();
+        self.labels.clear();
+        self.events.clear();
+    }
+
+    pub fn update(&mut self, field: &[f64], a_width: u32, a_height: u32, wrap: bool, generation: u64) {
+        let (labels, mut found) = label_components(field, a_width, a_height, wrap, self.threshold);
+        self.events.clear();
+
+        // Overlap between last generation's creatures and the new components,
+        // ordered so that ties go to the lower index and runs repeat exactly
+        let mut overlap: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+        if self.labels.len() == labels.len() {
+            for (&old, &new) in self.labels.iter().zip(labels.iter()) {
+                if old > 0 && new > 0 {
+                    *overlap.entry((old - 1, new - 1)).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut best_new: Vec<Option<(usize, usize)>> = vec![None; self.creatures.len()];
+        let mut best_old: Vec<Option<(usize, usize)>> = vec![None; found.len()];
+        for (&(old, new), &count) in &overlap {
+            if best_new[old].is_none_or(|(_, c)| count > c) {
+                best_new[old] = Some((new, count));
+            }
+            if best_old[new].is_none_or(|(_, c)| count > c) {
+                best_old[new] = Some((old, count));
+            }
+        }
+
+        let elapsed = generation.saturating_sub(self.last_generation).max(1) as f64;
+        let mut split_children = Vec::new();
+        for (n, creature) in found.iter_mut().enumerate() {
+            match best_old[n] {
+                // Mutual best match: the same organism one step later
+                Some((o, _)) if best_new[o].map(|(b, _)| b) == Some(n) => {
+                    let prev = &self.creatures[o];
+                    creature.id = prev.id;
+                    creature.born = prev.born;
+                    creature.velocity_x = wrap_delta(creature.centroid_x - prev.centroid_x, a_width, wrap) / elapsed;
+                    creature.velocity_y = wrap_delta(creature.centroid_y - prev.centroid_y, a_height, wrap) / elapsed;
+                }
+                Some((o, _)) => {
+                    creature.id = self.take_id();
+                    creature.born = generation;
+                    split_children.push((self.creatures[o].id, creature.id));
+                }
+                None => {
+                    creature.id = self.take_id();
+                    creature.born = generation;
+                    self.events.push(CreatureEvent::Birth { id: creature.id });
+                }
+            }
+        }
+        for (parent, child) in split_children {
+            self.events.push(CreatureEvent::Split { parent, child });
+        }
+
+        for (o, prev) in self.creatures.iter().enumerate() {
+            match best_new[o] {
+                None => self.events.push(CreatureEvent::Death { id: prev.id }),
+                Some((n, _)) if found[n].id != prev.id => {
+                    self.events.push(CreatureEvent::Merge { into: found[n].id, absorbed: prev.id });
+                }
+                Some(_) => {}
+            }
+        }
+
+        self.creatures = found;
+        self.labels = labels;
+        self.last_generation = generation;
+    }
+
+    fn take_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+// Shortest signed difference along an axis, going around the torus if needed
+fn wrap_delta(d: f64, size: u32, wrap: bool) -> f64 {
+    let size = size as f64;
+    if wrap {
+        d - size * (d / size).round()
+    } else {
+        d
+    }
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+// 4-connected components of cells above the threshold, joined across the
+// edges when wrapping; returns per-cell labels and the measured components
+fn label_components(field: &[f64], a_width: u32, a_height: u32, wrap: bool, threshold: f64) -> (Vec<usize>, Vec<Creature>) {
+    let (w, h) = (a_width as usize, a_height as usize);
+    let alive = |i: usize| field[i] > threshold;
+    let mut parent: Vec<usize> = (0..field.len()).collect();
+
+    for y in 0..h {
+        for x in 0..w {
+            let i = y * w + x;
+            if !alive(i) {
+                continue;
+            }
+            let right = if x + 1 < w { Some(i + 1) } else if wrap { Some(y * w) } else { None };
+            let down = if y + 1 < h { Some(i + w) } else if wrap { Some(x) } else { None };
+            for j in [right, down].into_iter().flatten() {
+                if alive(j) {
+                    let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                    if a != b {
+                        parent[a.max(b)] = a.min(b);
+                    }
+                }
+            }
+        }
+    }
+
+    // Group cells by root
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..field.len() {
+        if alive(i) {
+            let root = find(&mut parent, i);
+            members.entry(root).or_default().push(i);
+        }
+    }
+    let mut groups: Vec<Vec<usize>> = members.into_values().filter(|c| c.len() >= MIN_CREATURE_CELLS).collect();
+    groups.sort_by_key(|c| c[0]);
+
+    let mut labels = vec![0; field.len()];
+    for (n, cells) in groups.iter().enumerate() {
+        for &i in cells {
+            labels[i] = n + 1;
+        }
+    }
+
+    let creatures = groups
+        .iter()
+        .enumerate()
+        .map(|(n, cells)| measure(field, &labels, cells, n + 1, a_width, a_height, wrap))
+        .collect();
+    (labels, creatures)
+}
+
+fn measure(field: &[f64], labels: &[usize], cells: &[usize], label: usize, a_width: u32, a_height: u32, wrap: bool) -> Creature {
+    let (w, h) = (a_width as usize, a_height as usize);
+
+    // Positions are unwrapped relative to the first cell so a creature
+    // straddling the edge stays in one piece
+    let (x0, y0) = ((cells[0] % w) as f64, (cells[0] / w) as f64);
+    let mut mass = 0.0;
+    let (mut sum_x, mut sum_y) = (0.0, 0.0);
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    let mut perimeter = 0;
+
+    for &i in cells {
+        let (x, y) = (i % w, i / w);
+        let ux = x0 + wrap_delta(x as f64 - x0, a_width, wrap);
+        let uy = y0 + wrap_delta(y as f64 - y0, a_height, wrap);
+        mass += field[i];
+        sum_x += field[i] * ux;
+        sum_y += field[i] * uy;
+        min_x = min_x.min(ux);
+        min_y = min_y.min(uy);
+        max_x = max_x.max(ux);
+        max_y = max_y.max(uy);
+
+        let neighbours = [
+            (x as i64 - 1, y as i64),
+            (x as i64 + 1, y as i64),
+            (x as i64, y as i64 - 1),
+            (x as i64, y as i64 + 1),
+        ];
+        for (nx, ny) in neighbours {
+            let inside = nx >= 0 && ny >= 0 && (nx as usize) < w && (ny as usize) < h;
+            let same = if inside {
+                labels[ny as usize * w + nx as usize] == label
+            } else if wrap {
+                let (nx, ny) = (nx.rem_euclid(w as i64) as usize, ny.rem_euclid(h as i64) as usize);
+                labels[ny * w + nx] == label
+            } else {
+                false
+            };
+            if !same {
+                perimeter += 1;
+            }
+        }
+    }
+
+    let (mut centroid_x, mut centroid_y) = if mass > 0.0 { (sum_x / mass, sum_y / mass) } else { (x0, y0) };
+    if wrap {
+        // Bring the centroid back onto the field and move the box along with it
+        let (shift_x, shift_y) = (centroid_x.rem_euclid(a_width as f64) - centroid_x, centroid_y.rem_euclid(a_height as f64) - centroid_y);
+        centroid_x += shift_x;
+        centroid_y += shift_y;
+        min_x += shift_x;
+        max_x += shift_x;
+        min_y += shift_y;
+        max_y += shift_y;
+    }
+
+    Creature {
+        id: 0,
+        born: 0,
+        cells: cells.len(),
+        mass,
+        centroid_x,
+        centroid_y,
+        velocity_x: 0.0,
+        velocity_y: 0.0,
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+        perimeter,
+    }
+}
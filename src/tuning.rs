@@ -0,0 +1,285 @@
+use std::fs;
+
+pub const DEFAULT_ROOT_KEY: i32 = 48;
+pub const DEFAULT_OCTAVES: u32 = 3;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScaleName {
+    Major,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Minor,
+    Locrian,
+    HarmonicMinor,
+    PentatonicMajor,
+    PentatonicMinor,
+    WholeTone,
+    Chromatic,
+}
+
+pub const SCALES: [ScaleName; 12] = [
+    ScaleName::Major,
+    ScaleName::Dorian,
+    ScaleName::Phrygian,
+    ScaleName::Lydian,
+    ScaleName::Mixolydian,
+    ScaleName::Minor,
+    ScaleName::Locrian,
+    ScaleName::HarmonicMinor,
+    ScaleName::PentatonicMajor,
+    ScaleName::PentatonicMinor,
+    ScaleName::WholeTone,
+    ScaleName::Chromatic,
+];
+
+impl ScaleName {
+    // Semitone steps within one octave of 12-tone equal temperament
+    pub fn steps(self) -> Vec<usize> {
+        let major = [0, 2, 4, 5, 7, 9, 11];
+        let mode = |n: usize| -> Vec<usize> { (0..7).map(|i| (major[(i + n) % 7] + 12 - major[n]) % 12).collect() };
+        match self {
+            ScaleName::Major => mode(0),
+            ScaleName::Dorian => mode(1),
+            ScaleName::Phrygian => mode(2),
+            ScaleName::Lydian => mode(3),
+            ScaleName::Mixolydian => mode(4),
+            ScaleName::Minor => mode(5),
+            ScaleName::Locrian => mode(6),
+            ScaleName::HarmonicMinor => vec![0, 2, 3, 5, 7, 8, 11],
+            ScaleName::PentatonicMajor => vec![0, 2, 4, 7, 9],
+            ScaleName::PentatonicMinor => vec![0, 3, 5, 7, 10],
+            ScaleName::WholeTone => vec![0, 2, 4, 6, 8, 10],
+            ScaleName::Chromatic => (0..12).collect(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        SCALES.iter().copied().find(|s| format!("{:?}", s).eq_ignore_ascii_case(name))
+    }
+}
+
+// Which tuning degree each keyboard key plays, as in a Scala .kbm file
+#[derive(Clone)]
+pub struct KeyboardMap {
+    pub middle_key: i32, // Key that plays degree 0
+    pub reference_key: i32,
+    pub reference_freq: f64,
+    pub mapping: Vec<Option<i64>>, // Empty for a linear key -> degree mapping
+    pub period_degree: i64,        // Degrees per repetition of the mapping
+}
+
+impl KeyboardMap {
+    pub fn standard() -> Self {
+        Self {
+            middle_key: 60,
+            reference_key: 69,
+            reference_freq: 440.0,
+            mapping: Vec::new(),
+            period_degree: 0,
+        }
+    }
+
+    fn degree(&self, key: i32) -> Option<i64> {
+        let offset = (key - self.middle_key) as i64;
+        if self.mapping.is_empty() {
+            return Some(offset);
+        }
+        let size = self.mapping.len() as i64;
+        let degree = self.mapping[offset.rem_euclid(size) as usize]?;
+        Some(degree + offset.div_euclid(size) * self.period_degree)
+    }
+}
+
+// Pitches of one period in cents (the first is always 0) plus how keys map onto them
+#[derive(Clone)]
+pub struct Tuning {
+    pub name: String,
+    pub cents: Vec<f64>,
+    pub period: f64,
+    pub keyboard: KeyboardMap,
+}
+
+impl Tuning {
+    pub fn equal_temperament(divisions: usize) -> Self {
+        Self {
+            name: format!("{}-TET", divisions),
+            cents: (0..divisions).map(|i| i as f64 * 1200.0 / divisions as f64).collect(),
+            period: 1200.0,
+            keyboard: KeyboardMap::standard(),
+        }
+    }
+
+    pub fn is_12_tet(&self) -> bool {
+        self.cents.len() == 12 && self.period == 1200.0 && self.keyboard.mapping.is_empty()
+    }
+
+    pub fn degree_cents(&self, degree: i64) -> f64 {
+        let n = self.cents.len() as i64;
+        self.period * degree.div_euclid(n) as f64 + self.cents[degree.rem_euclid(n) as usize]
+    }
+
+    pub fn degree_freq(&self, degree: i64) -> f64 {
+        let reference = self.keyboard.degree(self.keyboard.reference_key).unwrap_or(0);
+        self.keyboard.reference_freq * 2f64.powf((self.degree_cents(degree) - self.degree_cents(reference)) / 1200.0)
+    }
+
+    pub fn key_degree(&self, key: i32) -> Option<i64> {
+        self.keyboard.degree(key)
+    }
+
+    // Load a Scala scale and optionally a keyboard mapping
+    pub fn load_scala(scl_path: &str, kbm_path: Option<&str>) -> Result<Self, String> {
+        let text = fs::read_to_string(scl_path).map_err(|e| format!("{}: {}", scl_path, e))?;
+        let mut tuning = parse_scl(&text).map_err(|e| format!("{}: {}", scl_path, e))?;
+        if let Some(kbm_path) = kbm_path {
+            let text = fs::read_to_string(kbm_path).map_err(|e| format!("{}: {}", kbm_path, e))?;
+            tuning.keyboard = parse_kbm(&text).map_err(|e| format!("{}: {}", kbm_path, e))?;
+        }
+        Ok(tuning)
+    }
+}
+
+// Scala lines starting with '!' are comments
+fn scala_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().filter(|l| !l.trim_start().starts_with('!'))
+}
+
+fn parse_pitch(token: &str) -> Result<f64, String> {
+    if token.contains('.') {
+        token.parse::<f64>().map_err(|_| format!("bad pitch '{}'", token))
+    } else {
+        let (num, den) = token.split_once('/').unwrap_or((token, "1"));
+        let num: f64 = num.parse().map_err(|_| format!("bad ratio '{}'", token))?;
+        let den: f64 = den.parse().map_err(|_| format!("bad ratio '{}'", token))?;
+        if num <= 0.0 || den <= 0.0 {
+            return Err(format!("bad ratio '{}'", token));
+        }
+        Ok(1200.0 * (num / den).log2())
+    }
+}
+
+fn parse_scl(text: &str) -> Result<Tuning, String> {
+    let mut lines = scala_lines(text);
+    let name = lines.next().ok_or("missing description")?.trim().to_string();
+    let count: usize = lines
+        .next()
+        .and_then(|l| l.split_whitespace().next())
+        .and_then(|t| t.parse().ok())
+        .ok_or("missing note count")?;
+
+    let mut pitches = Vec::with_capacity(count);
+    for line in lines.filter(|l| !l.trim().is_empty()).take(count) {
+        let token = line.split_whitespace().next().ok_or("empty pitch")?;
+        pitches.push(parse_pitch(token)?);
+    }
+    if pitches.len() != count || count == 0 {
+        return Err(format!("expected {} pitches, found {}", count, pitches.len()));
+    }
+
+    // The last pitch is the period; degree 0 (1/1) is implicit
+    let period = pitches.pop().unwrap_or(1200.0);
+    let mut cents = vec![0.0];
+    cents.extend(pitches);
+    Ok(Tuning {
+        name: if name.is_empty() { "Scala".to_string() } else { name },
+        cents,
+        period,
+        keyboard: KeyboardMap::standard(),
+    })
+}
+
+fn parse_kbm(text: &str) -> Result<KeyboardMap, String> {
+    let mut values = scala_lines(text).map(|l| l.split_whitespace().next().unwrap_or("")).filter(|t| !t.is_empty());
+    let mut next = |what: &str| values.next().map(str::to_string).ok_or(format!("missing {}", what));
+
+    let size: usize = next("map size")?.parse().map_err(|_| "bad map size")?;
+    let _first_key = next("first note")?;
+    let _last_key = next("last note")?;
+    let middle_key: i32 = next("middle note")?.parse().map_err(|_| "bad middle note")?;
+    let reference_key: i32 = next("reference note")?.parse().map_err(|_| "bad reference note")?;
+    let reference_freq: f64 = next("reference frequency")?.parse().map_err(|_| "bad reference frequency")?;
+    let period_degree: i64 = next("octave degree")?.parse().map_err(|_| "bad octave degree")?;
+
+    let mut mapping = Vec::with_capacity(size);
+    for _ in 0..size {
+        // Missing trailing entries and 'x' are unmapped keys
+        mapping.push(next("mapping").ok().and_then(|t| t.parse().ok()));
+    }
+
+    Ok(KeyboardMap {
+        middle_key,
+        reference_key,
+        reference_freq,
+        mapping,
+        period_degree,
+    })
+}
+
+// Turns a continuous 0..1 value into a note of a scale over a range of octaves
+#[derive(Clone)]
+pub struct Quantiser {
+    pub tuning: Tuning,
+    pub scale: ScaleName,
+    pub root_key: i32,
+    pub octaves: u32,
+}
+
+impl Quantiser {
+    pub fn new(tuning: Tuning) -> Self {
+        Self {
+            tuning,
+            scale: ScaleName::PentatonicMajor,
+            root_key: DEFAULT_ROOT_KEY,
+            octaves: DEFAULT_OCTAVES,
+        }
+    }
+
+    // Named scales only make sense in 12-TET; other tunings use all their degrees
+    pub fn steps(&self) -> Vec<usize> {
+        if self.tuning.is_12_tet() {
+            self.scale.steps()
+        } else {
+            (0..self.tuning.cents.len()).collect()
+        }
+    }
+
+    pub fn degree(&self, value: f64) -> i64 {
+        let steps = self.steps();
+        let total = steps.len() * self.octaves.max(1) as usize;
+        let step = (value.clamp(0.0, 1.0) * (total - 1) as f64).round() as usize;
+        let root = self.tuning.key_degree(self.root_key).unwrap_or(0);
+        root + (step / steps.len() * self.tuning.cents.len() + steps[step % steps.len()]) as i64
+    }
+
+    pub fn freq(&self, value: f64) -> f64 {
+        self.tuning.degree_freq(self.degree(value))
+    }
+
+    // Nearest MIDI note and the remaining offset in cents for microtonal tunings
+    pub fn midi_note(&self, value: f64) -> (u8, f64) {
+        let exact = 69.0 + 12.0 * (self.freq(value) / 440.0).log2();
+        let note = exact.round().clamp(0.0, 127.0);
+        (note as u8, (exact - note) * 100.0)
+    }
+
+    pub fn cycle_scale(&mut self) {
+        let idx = SCALES.iter().position(|&s| s == self.scale).unwrap_or(0);
+        self.scale = SCALES[(idx + 1) % SCALES.len()];
+    }
+
+    pub fn change_root(&mut self, delta: i32) {
+        self.root_key = (self.root_key + delta).clamp(0, 127);
+    }
+
+    pub fn describe(&self) -> String {
+        let names = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+        let root = format!("{}{}", names[self.root_key.rem_euclid(12) as usize], self.root_key / 12 - 1);
+        if self.tuning.is_12_tet() {
+            format!("{} {:?} x{}", root, self.scale, self.octaves)
+        } else {
+            format!("{} {} x{}", root, self.tuning.name, self.octaves)
+        }
+    }
+}
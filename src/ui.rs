@@ -1,8 +1,14 @@
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
 use sdl2::EventPump;
 use sdl2::VideoSubsystem;
 use crate::game::GameOfLife;
+use crate::camera::ZOOM_STEP;
+use crate::modulation::EnvelopeTrigger;
+use crate::session::SESSION_PATH;
+use crate::image_io::{ImageChannel, ImageMode, FIELD_IMAGE_PATH};
+use crate::npy::{layer_npy_path, NpyDtype};
 
 pub fn handle_events(event_pump: &mut EventPump, game: &mut GameOfLife, video_subsystem: &VideoSubsystem) -> bool {
     for event in event_pump.poll_iter() {
@@ -12,9 +18,106 @@ pub fn handle_events(event_pump: &mut EventPump, game: &mut GameOfLife, video_su
             Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                 return false;
             },
+            Event::KeyDown { keycode: Some(key), keymod, .. }
+                if game.library.open && !keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) && game.library.handle_key(key) => {},
+            Event::KeyDown { keycode: Some(Keycode::Tab), .. } => {
+                game.toggle_library();
+            },
+            Event::KeyDown { keycode: Some(Keycode::Z), keymod, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                game.undo();
+            },
+            Event::KeyDown { keycode: Some(Keycode::Y), keymod, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                game.redo();
+            },
+            Event::KeyDown { keycode: Some(Keycode::S), keymod, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                game.save_session(SESSION_PATH);
+            },
+            Event::KeyDown { keycode: Some(Keycode::L), keymod, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                if let Err(e) = game.load_session(SESSION_PATH) {
+                    eprintln!("Failed to load session: {}", e);
+                }
+            },
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                game.new_world();
+            },
+            Event::KeyDown { keycode: Some(key @ (Keycode::Num1 | Keycode::Num2 | Keycode::Num3 | Keycode::Num4
+                | Keycode::Num5 | Keycode::Num6 | Keycode::Num7 | Keycode::Num8)), .. } => {
+                game.select_init((key.into_i32() - Keycode::Num1.into_i32()) as usize);
+            },
+            Event::KeyDown { keycode: Some(Keycode::E), keymod, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                match game.save_field_pgm(FIELD_IMAGE_PATH) {
+                    Ok(()) => println!("Saved field to {}", FIELD_IMAGE_PATH),
+                    Err(e) => eprintln!("Failed to save field: {}", e),
+                }
+            },
+            Event::KeyDown { keycode: Some(Keycode::I), keymod, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                if let Err(e) = game.load_field_image(FIELD_IMAGE_PATH, ImageMode::Channel(ImageChannel::Luma)) {
+                    eprintln!("Failed to load field: {}", e);
+                }
+            },
+            // .npy export and import of the layer being shown
+            Event::KeyDown { keycode: Some(Keycode::D), keymod, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                let path = layer_npy_path(game.layer);
+                match game.save_npy(game.layer, &path, NpyDtype::F64) {
+                    Ok(()) => println!("Saved {}", path),
+                    Err(e) => eprintln!("Failed to save .npy: {}", e),
+                }
+            },
+            Event::KeyDown { keycode: Some(Keycode::O), keymod, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                if let Err(e) = game.load_npy(game.layer, &layer_npy_path(game.layer)) {
+                    eprintln!("Failed to load .npy: {}", e);
+                }
+            },
+            Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                game.modulation.trigger_envelopes(EnvelopeTrigger::Key);
+            },
             Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
                 game.running = !game.running;
             },
+            Event::KeyDown { keycode: Some(Keycode::Right), .. } if !game.running => {
+                game.step_forward();
+            },
+            Event::KeyDown { keycode: Some(Keycode::Left), keymod, .. } => {
+                game.running = false;
+                let steps = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) { 10 } else { 1 };
+                game.step_back(steps);
+            },
+            Event::KeyDown { keycode: Some(Keycode::Period), .. } => {
+                game.change_speed(true);
+            },
+            Event::KeyDown { keycode: Some(Keycode::Comma), .. } => {
+                game.change_speed(false);
+            },
+            Event::KeyDown { keycode: Some(Keycode::T), .. } => {
+                game.tap_tempo();
+            },
+            Event::KeyDown { keycode: Some(Keycode::X), .. } => {
+                game.cycle_spectral();
+            },
+            Event::KeyDown { keycode: Some(Keycode::P), .. } => {
+                game.toggle_granular();
+            },
+            Event::KeyDown { keycode: Some(Keycode::A), .. } => {
+                game.follow_creature = !game.follow_creature;
+            },
+            Event::KeyDown { keycode: Some(Keycode::J), .. } => {
+                game.cycle_period_target();
+            },
+            Event::KeyDown { keycode: Some(Keycode::L), .. } => {
+                game.cycle_layer();
+            },
+            Event::KeyDown { keycode: Some(Keycode::Y), .. } => {
+                game.cycle_mask_brush();
+            },
+            Event::KeyDown { keycode: Some(Keycode::D), .. } => {
+                game.clear_layer();
+            },
+            Event::KeyDown { keycode: Some(Keycode::U), .. } => {
+                game.cycle_midi_learn();
+            },
+            Event::KeyDown { keycode: Some(Keycode::K), .. } => {
+                game.cycle_steps_per_sixteenth();
+            },
             Event::KeyDown { keycode: Some(Keycode::H), .. } => {
                 game.toggle_info_window(video_subsystem);
             },
@@ -33,6 +136,12 @@ pub fn handle_events(event_pump: &mut EventPump, game: &mut GameOfLife, video_su
             Event::KeyDown { keycode: Some(Keycode::C), .. } => {
                 game.switch_gradient();
             },
+            Event::KeyDown { keycode: Some(Keycode::N), keymod, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                game.fill_with_noise();
+            },
+            Event::KeyDown { keycode: Some(Keycode::N), keymod, .. } if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                game.cycle_noise_model();
+            },
             Event::KeyDown { keycode: Some(Keycode::N), .. } => {
                 game.toggle_noise();
             },
@@ -66,26 +175,109 @@ pub fn handle_events(event_pump: &mut EventPump, game: &mut GameOfLife, video_su
             Event::KeyDown { keycode: Some(Keycode::F10), .. } => {
                 game.change_parameter("noise_intensity", -0.01);
             },
+            Event::KeyDown { keycode: Some(Keycode::F), .. } => {
+                game.fit_to_window();
+            },
+            Event::KeyDown { keycode: Some(Keycode::LCtrl | Keycode::RCtrl), .. } => {
+                game.ctrl_held = true;
+            },
+            Event::KeyUp { keycode: Some(Keycode::LCtrl | Keycode::RCtrl), .. } => {
+                game.ctrl_held = false;
+            },
+            Event::KeyDown { keycode: Some(Keycode::LeftBracket), .. } => {
+                game.brush.change_radius(-1);
+            },
+            Event::KeyDown { keycode: Some(Keycode::RightBracket), .. } => {
+                game.brush.change_radius(1);
+            },
+            Event::KeyDown { keycode: Some(Keycode::Minus), .. } => {
+                game.brush.change_value(-0.05);
+            },
+            Event::KeyDown { keycode: Some(Keycode::Equals), .. } => {
+                game.brush.change_value(0.05);
+            },
+            Event::KeyDown { keycode: Some(Keycode::B), .. } => {
+                game.brush.cycle_shape();
+            },
+            Event::KeyDown { keycode: Some(Keycode::G), .. } => {
+                game.brush.toggle_falloff();
+            },
+            Event::KeyDown { keycode: Some(Keycode::M), .. } => {
+                game.brush.cycle_mode();
+            },
+            Event::MouseWheel { y, .. } if game.ctrl_held => {
+                game.brush.change_radius(y.signum());
+            },
+            Event::MouseWheel { y, .. } => {
+                let (mouse_x, mouse_y) = game.mouse_pos;
+                let factor = if y > 0 { ZOOM_STEP } else { 1.0 / ZOOM_STEP };
+                game.camera.zoom_at(mouse_x, mouse_y, factor);
+            },
+            Event::KeyDown { keycode: Some(Keycode::F11), .. } => {
+                game.change_parameter("dt", 0.01);
+            },
+            Event::KeyDown { keycode: Some(Keycode::F12), .. } => {
+                game.change_parameter("dt", -0.01);
+            },
+            Event::KeyDown { keycode: Some(Keycode::W), .. } => {
+                game.toggle_wrap();
+            },
+            Event::KeyDown { keycode: Some(Keycode::V), .. } => {
+                game.toggle_sound();
+            },
+            Event::KeyDown { keycode: Some(Keycode::Q), .. } => {
+                game.quantiser.cycle_scale();
+            },
+            Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => {
+                game.quantiser.change_root(1);
+            },
+            Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => {
+                game.quantiser.change_root(-1);
+            },
+            Event::KeyDown { keycode: Some(Keycode::O), .. } => {
+                game.show_creatures = !game.show_creatures;
+            },
+            Event::KeyDown { keycode: Some(Keycode::E), .. } => {
+                game.export_stats();
+            },
+            Event::KeyDown { keycode: Some(Keycode::I), .. } => {
+                game.cycle_integrator();
+            },
+            Event::MouseButtonDown { mouse_btn: MouseButton::Middle, .. } => {
+                game.panning = true;
+            },
+            Event::MouseButtonUp { mouse_btn: MouseButton::Middle, .. } => {
+                game.panning = false;
+            },
+            Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } if game.library.open => {
+                game.library_click(x, y);
+            },
             Event::MouseButtonDown { mouse_btn, x, y, .. } => {
-                game.mouse_down = true;
-                let state = match mouse_btn {
-                    sdl2::mouse::MouseButton::Left => 1.0,
-                    sdl2::mouse::MouseButton::Right => 0.0,
+                game.erasing = match mouse_btn {
+                    MouseButton::Left => false,
+                    MouseButton::Right => true,
                     _ => continue,
                 };
-                game.add_cells_with_brush(x, y, 5, state);
+                game.mouse_down = true;
+                game.record_history(); // One undo step per brush stroke
+                game.add_cells_with_brush(x, y, game.erasing);
             },
-            Event::MouseButtonUp { mouse_btn: sdl2::mouse::MouseButton::Left, .. }
-            | Event::MouseButtonUp { mouse_btn: sdl2::mouse::MouseButton::Right, .. } => {
+            Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. }
+            | Event::MouseButtonUp { mouse_btn: MouseButton::Right, .. } => {
                 game.mouse_down = false;
             },
+            Event::MouseMotion { x, y, xrel, yrel, .. } if game.panning => {
+                game.mouse_pos = (x, y);
+                game.camera.pan(xrel, yrel);
+            },
             Event::MouseMotion { x, y, .. } => {
+                game.mouse_pos = (x, y);
                 if game.mouse_down {
-                    game.add_cells_with_brush(x, y, 5, 1.0);
+                    game.add_cells_with_brush(x, y, game.erasing);
                 }
             },
             Event::Window { win_event: sdl2::event::WindowEvent::Resized(new_width, new_height), .. } => {
-                game.resize(new_width as u32, new_height as u32);
+                game.set_view_size(new_width as u32, new_height as u32);
             },
             _ => {}
         }
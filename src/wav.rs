@@ -0,0 +1,127 @@
+use std::fs;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+pub struct WavData {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>, // Mono mixdown in -1..1
+}
+
+const PCM: u16 = 1;
+const FLOAT: u16 = 3;
+const EXTENSIBLE: u16 = 0xFFFE;
+
+// None past the end of a truncated file
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+// Reads RIFF/WAVE files with 8/16/24/32-bit integer or 32-bit float PCM,
+// plain or WAVE_FORMAT_EXTENSIBLE
+pub fn read_wav(path: &str) -> Result<WavData, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(format!("{}: not a WAV file", path));
+    }
+
+    let mut format = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32_at(&data, pos + 4).ok_or(format!("{}: truncated chunk header", path))? as usize;
+        let body = pos + 8;
+        let end = (body + size).min(data.len());
+
+        if id == b"fmt " && size >= 16 {
+            let truncated = || format!("{}: truncated fmt chunk", path);
+            let mut tag = u16_at(&data, body).ok_or_else(truncated)?;
+            // Extensible files keep the real format in the first two bytes of the SubFormat GUID
+            if tag == EXTENSIBLE {
+                tag = if size >= 40 { u16_at(&data, body + 24).ok_or_else(truncated)? } else { return Err(truncated()) };
+            }
+            // (tag, channels, sample rate, bits per sample)
+            format = Some((
+                tag,
+                u16_at(&data, body + 2).ok_or_else(truncated)?,
+                u32_at(&data, body + 4).ok_or_else(truncated)?,
+                u16_at(&data, body + 14).ok_or_else(truncated)?,
+            ));
+        } else if id == b"data" {
+            let (tag, channels, sample_rate, bits) = format.ok_or(format!("{}: data before fmt chunk", path))?;
+            let samples = decode_samples(&data[body..end], tag, channels.max(1) as usize, bits)
+                .ok_or(format!("{}: unsupported format (tag {}, {} bits)", path, tag, bits))?;
+            return Ok(WavData { sample_rate, samples });
+        }
+        // Chunks are padded to an even size
+        pos = body + size + (size & 1);
+    }
+    Err(format!("{}: no data chunk", path))
+}
+
+fn decode_samples(bytes: &[u8], tag: u16, channels: usize, bits: u16) -> Option<Vec<f32>> {
+    let width = (bits / 8) as usize;
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (PCM, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+        (PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+        (PCM, 24) => |b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0,
+        (PCM, 32) => |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0,
+        (FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        _ => return None,
+    };
+
+    Some(
+        bytes
+            .chunks_exact(width * channels)
+            .map(|frame| frame.chunks_exact(width).map(decode).sum::<f32>() / channels as f32)
+            .collect(),
+    )
+}
+
+// Streams interleaved stereo 16-bit PCM; the header sizes are patched in finish()
+pub struct WavWriter {
+    out: BufWriter<File>,
+    frames: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &str, sample_rate: u32) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let channels: u16 = 2;
+        let bits: u16 = 16;
+        out.write_all(b"RIFF")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&channels.to_le_bytes())?;
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&(sample_rate * channels as u32 * bits as u32 / 8).to_le_bytes())?;
+        out.write_all(&(channels * bits / 8).to_le_bytes())?;
+        out.write_all(&bits.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        Ok(Self { out, frames: 0 })
+    }
+
+    pub fn write(&mut self, interleaved: &[f32]) -> io::Result<()> {
+        for s in interleaved {
+            let v = (s.clamp(-1.0, 1.0) * 32767.0) as i16;
+            self.out.write_all(&v.to_le_bytes())?;
+        }
+        self.frames += (interleaved.len() / 2) as u32;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        let data_bytes = self.frames * 4;
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + data_bytes).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&data_bytes.to_le_bytes())?;
+        self.out.flush()
+    }
+}